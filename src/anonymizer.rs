@@ -0,0 +1,1058 @@
+use image::{DynamicImage, GenericImageView, ImageFormat, ImageOutputFormat};
+use std::borrow::Cow;
+use std::env;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use tracing::{debug, error, info, warn};
+
+use crate::client::ApiClient;
+use crate::error::{Error, Result};
+use crate::face::detection::{FaceAnnotation, FaceDetector, VisionFaceDetector};
+use crate::face::masking::face_region;
+use crate::mask::{MaskRegion, MaskStyle};
+use crate::ocr::classifier::Verdict;
+use crate::ocr::classifier::{ChainClassifier, RuleBasedClassifier, SensitivityClassifier};
+use crate::ocr::detection::{TextAnnotation, TextDetector, VisionTextDetector};
+use crate::ocr::document::{SymbolBox, TextGranularity};
+use crate::ocr::gemini::{DEFAULT_GEMINI_MODEL, GeminiClassifier};
+use crate::ocr::mask_list::{MaskList, MaskPattern};
+use crate::ocr::masking::{
+    DEFAULT_MAX_BOX_RATIO, SensitiveTextCriteria, TextMask, annotation_region, masked_characters,
+    masked_parts, resolve_masks, try_classify_annotations,
+};
+use crate::ocr::secret_hashes::SecretHashes;
+use crate::report::{
+    FaceFinding, Report, Stage, StageFailure, StageTimings, TextFinding, sha256_hex,
+};
+use crate::vision::{AnnotateImageResponse, MAX_BATCH_SIZE, VisionClient, VisionFeature};
+
+/// Reusable image anonymization pipeline
+///
+/// An `Anonymizer` holds the detection and classification backends together
+/// with the masking options. It is built once with [`Anonymizer::builder`]
+/// and can then process any number of images, from several threads if needed.
+///
+/// # Example
+///
+/// ```no_run
+/// use image_anonymizer::Anonymizer;
+/// use std::path::{Path, PathBuf};
+///
+/// let anonymizer = Anonymizer::builder()
+///     .api_key("my-gcp-api-key")
+///     .mask_texts(["confidential"])
+///     .mask_faces(true)
+///     .build()?;
+///
+/// anonymizer.process_file(Path::new("screenshot.png"), Path::new("./output"))?;
+/// # Ok::<(), image_anonymizer::Error>(())
+/// ```
+pub struct Anonymizer {
+    text_detector: Box<dyn TextDetector>,
+    face_detector: Option<Box<dyn FaceDetector>>,
+    /// Set when the default Vision backends are used, to detect text (and
+    /// faces, with `vision_faces`) with a single request per image
+    vision: Option<VisionClient>,
+    vision_faces: bool,
+    /// Granularity of the text found with `DOCUMENT_TEXT_DETECTION`, `None`
+    /// for plain `TEXT_DETECTION`
+    document_text: Option<TextGranularity>,
+    min_text_confidence: f32,
+    batch_size: usize,
+    on_failure: FailurePolicy,
+    classifier: Box<dyn SensitivityClassifier>,
+    criteria: SensitiveTextCriteria,
+    mask_list: MaskList,
+    text_mask_style: MaskStyle,
+    face_mask_style: MaskStyle,
+    max_text_box_ratio: f32,
+    min_face_confidence: f32,
+    output_prefix: String,
+}
+
+impl Anonymizer {
+    /// Start configuring a new anonymizer
+    pub fn builder() -> AnonymizerBuilder {
+        AnonymizerBuilder::default()
+    }
+
+    /// Mask an encoded image held in memory
+    ///
+    /// The image format is detected from the bytes and the masked image is
+    /// encoded back in the same format. Nothing is read from or written to disk.
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The encoded image (PNG, JPEG, ...)
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<u8>>` - The encoded masked image
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If the image cannot be decoded, processed or encoded
+    ///
+    pub fn anonymize_bytes(&self, bytes: &[u8]) -> Result<Vec<u8>> {
+        let (output, _) = self.anonymize_bytes_with_report(bytes)?;
+        Ok(output)
+    }
+
+    /// Mask an encoded image held in memory and report what was masked
+    ///
+    /// # Arguments
+    ///
+    /// * `bytes` - The encoded image (PNG, JPEG, ...)
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(Vec<u8>, Report)>` - The encoded masked image and what was masked
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If the image cannot be decoded, processed or encoded
+    ///
+    pub fn anonymize_bytes_with_report(&self, bytes: &[u8]) -> Result<(Vec<u8>, Report)> {
+        let format = image::guess_format(bytes).map_err(Error::Decode)?;
+        self.anonymize_encoded(bytes, format)
+    }
+
+    /// Mask a decoded image, leaving the original untouched
+    ///
+    /// # Arguments
+    ///
+    /// * `img` - The image to mask
+    ///
+    /// # Returns
+    ///
+    /// * `Result<(DynamicImage, Report)>` - The masked copy and what was masked
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If text detection or masking fails
+    ///
+    pub fn anonymize_image(&self, img: &DynamicImage) -> Result<(DynamicImage, Report)> {
+        let mut masked = img.clone();
+        let report = self.mask(&mut masked)?;
+        Ok((masked, report))
+    }
+
+    /// Mask sensitive text and faces in an image, in place
+    ///
+    /// # Arguments
+    ///
+    /// * `img` - The image to mask
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Report>` - Everything that was detected and masked
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If text detection or masking fails
+    ///
+    pub fn mask(&self, img: &mut DynamicImage) -> Result<Report> {
+        self.mask_with_source(img, None)
+    }
+
+    /// Mask an image in place, given the encoded bytes it was decoded from if any
+    fn mask_with_source(&self, img: &mut DynamicImage, encoded: Option<&[u8]>) -> Result<Report> {
+        let detections = self.detect(img, encoded);
+        self.mask_detected(img, detections)
+    }
+
+    /// Find text, and faces when they come from the same Vision request
+    fn detect(&self, img: &DynamicImage, encoded: Option<&[u8]>) -> Result<Detections> {
+        let started = Instant::now();
+        let detections = match &self.vision {
+            Some(vision) => {
+                let response =
+                    vision.annotate(&vision_payload(img, encoded)?, &self.vision_features())?;
+                self.vision_detections(response, started)
+            }
+            None => Detections {
+                texts: self.text_detector.detect_text(img)?,
+                symbols: Vec::new(),
+                faces: None,
+                elapsed_ms: StageTimings::millis(started.elapsed()),
+            },
+        };
+        Ok(detections)
+    }
+
+    /// Detect several images, with batched requests when the Vision API is used
+    ///
+    /// Returns one result per image. If a batched request fails as a whole,
+    /// each image is sent again on its own so that a single bad image does
+    /// not fail the others.
+    fn detect_many(&self, images: &[(&DynamicImage, &[u8])]) -> Vec<Result<Detections>> {
+        let detect_each = || {
+            images
+                .iter()
+                .map(|(img, bytes)| self.detect(img, Some(bytes)))
+                .collect()
+        };
+
+        let Some(vision) = &self.vision else {
+            return detect_each();
+        };
+        if images.len() < 2 {
+            return detect_each();
+        }
+
+        let started = Instant::now();
+        let payloads = match images
+            .iter()
+            .map(|(img, bytes)| vision_payload(img, Some(bytes)))
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(payloads) => payloads,
+            Err(e) => {
+                warn!("Failed to prepare batched Vision request: {}", e);
+                return detect_each();
+            }
+        };
+        let payloads: Vec<&[u8]> = payloads.iter().map(AsRef::as_ref).collect();
+
+        match vision.annotate_batch(&payloads, &self.vision_features()) {
+            Ok(responses) => responses
+                .into_iter()
+                .map(|response| Ok(self.vision_detections(response?, started)))
+                .collect(),
+            Err(e) => {
+                warn!(
+                    "Batched Vision request for {} images failed, retrying one by one: {}",
+                    images.len(),
+                    e
+                );
+                detect_each()
+            }
+        }
+    }
+
+    /// Features requested from the Vision API for each image
+    fn vision_features(&self) -> Vec<VisionFeature> {
+        let mut features = vec![match self.document_text {
+            Some(_) => VisionFeature::DocumentTextDetection,
+            None => VisionFeature::TextDetection,
+        }];
+        if self.vision_faces {
+            features.push(VisionFeature::FaceDetection);
+        }
+        features
+    }
+
+    /// Detections of one Vision API response
+    fn vision_detections(
+        &self,
+        mut response: AnnotateImageResponse,
+        started: Instant,
+    ) -> Detections {
+        if self.document_text.is_some()
+            && let Some(document) = &mut response.full_text_annotation
+        {
+            document.retain_confident(self.min_text_confidence);
+        }
+        let (texts, symbols) = response
+            .texts_with_symbols(self.document_text)
+            .into_iter()
+            .unzip();
+        Detections {
+            texts,
+            symbols,
+            faces: self.vision_faces.then_some(response.face_annotations),
+            elapsed_ms: StageTimings::millis(started.elapsed()),
+        }
+    }
+
+    /// Classify the detected text and mask the image in place
+    ///
+    /// Backend failures are handled according to the failure policy: recorded
+    /// in the report, returned as an error, or answered by blacking out the
+    /// whole image.
+    fn mask_detected(
+        &self,
+        img: &mut DynamicImage,
+        detections: Result<Detections>,
+    ) -> Result<Report> {
+        let mut report = Report {
+            text_detector: self.text_detector.name().to_string(),
+            ..Report::default()
+        };
+        let (width, height) = img.dimensions();
+
+        let detections = match detections {
+            Ok(detections) => detections,
+            // Without text detection nothing can be masked, so only a blackout helps
+            Err(e) if self.on_failure == FailurePolicy::Blackout => {
+                self.record_failure(&mut report, Stage::TextDetection, e)?;
+                return Ok(black_out(img, report));
+            }
+            Err(e) => return Err(e),
+        };
+        let annotations = detections.texts;
+        let symbols = detections.symbols;
+        report.timings.text_detection_ms = detections.elapsed_ms;
+
+        // Detect faces before any masking so detectors always see the original image
+        let started = Instant::now();
+        let face_annotations = match detections.faces {
+            Some(faces) => faces,
+            None => match self.detect_faces(img) {
+                Ok(faces) => faces,
+                Err(e) => {
+                    if self.record_failure(&mut report, Stage::FaceDetection, e)? {
+                        return Ok(black_out(img, report));
+                    }
+                    info!("Skipping face masking due to detection error");
+                    Vec::new()
+                }
+            },
+        };
+        report.timings.face_detection_ms = StageTimings::millis(started.elapsed());
+
+        let started = Instant::now();
+        let results = if annotations.is_empty() {
+            debug!("No text detected in the image");
+            Vec::new()
+        } else {
+            debug!("Detected {} text annotations", annotations.len());
+            try_classify_annotations(
+                &annotations,
+                &self.mask_list,
+                &self.criteria,
+                self.classifier.as_ref(),
+            )
+        };
+        let mut verdicts = Vec::with_capacity(results.len());
+        for result in results {
+            let verdict = match result {
+                Some(Err(e)) => {
+                    let classifier = self.classifier.name().to_string();
+                    let reason = format!("classifier failed: {}", e);
+                    if self.record_failure(&mut report, Stage::Classification, e)? {
+                        return Ok(black_out(img, report));
+                    }
+                    Some(Verdict::not_sensitive(reason, classifier))
+                }
+                Some(Ok(verdict)) => Some(verdict),
+                None => None,
+            };
+            verdicts.push(verdict);
+        }
+        let masks = resolve_masks(
+            &annotations,
+            &mut verdicts,
+            &self.mask_list,
+            &self.criteria,
+            self.classifier.as_ref(),
+        );
+        report.timings.classification_ms = StageTimings::millis(started.elapsed());
+
+        let started = Instant::now();
+        if !annotations.is_empty() {
+            info!("Masking sensitive text in image");
+        }
+        let findings = annotations.iter().zip(verdicts).zip(masks).enumerate();
+        for (index, ((annotation, verdict), text_mask)) in findings {
+            let mut mask = match text_mask {
+                TextMask::Keep => None,
+                _ => annotation_region(annotation, width, height, self.max_text_box_ratio),
+            };
+
+            // Mask only the sensitive characters when they can be located
+            let symbols = symbols.get(index).map_or(&[][..], Vec::as_slice);
+            let by_character = self.document_text == Some(TextGranularity::Character);
+            let masked_parts = match (text_mask, mask) {
+                (TextMask::Whole, Some(_)) if by_character => {
+                    let whole = 0..annotation.description.len();
+                    masked_characters(annotation, symbols, &[whole], width, height)
+                }
+                (TextMask::Parts(spans), Some(_)) if by_character => {
+                    masked_characters(annotation, symbols, &spans, width, height)
+                }
+                (TextMask::Parts(spans), Some(_)) => {
+                    masked_parts(annotation, symbols, &spans, width, height)
+                }
+                _ => Vec::new(),
+            };
+            if let Some(first) = masked_parts.first() {
+                mask = Some(
+                    masked_parts
+                        .iter()
+                        .fold(first.region, |region, part| region.union(&part.region)),
+                );
+                for part in &masked_parts {
+                    self.text_mask_style.apply(img, &part.region);
+                }
+            } else if let Some(region) = &mask {
+                self.text_mask_style.apply(img, region);
+            }
+
+            report.texts.push(TextFinding {
+                text: annotation.description.clone(),
+                bounds: annotation.bounding_poly.as_ref().and_then(|poly| {
+                    MaskRegion::from_points(poly.vertices.iter().map(|v| (v.x, v.y)), width, height)
+                }),
+                verdict,
+                mask,
+                masked_parts,
+            });
+        }
+        if !annotations.is_empty() {
+            info!(
+                "Masked {} sensitive text regions",
+                report.masked_text_regions().count()
+            );
+        }
+
+        if self.face_detector.is_some() {
+            if face_annotations.is_empty() {
+                info!("No faces detected in the image");
+            } else {
+                info!("Detected {} faces in the image", face_annotations.len());
+            }
+        }
+        let face_detector_name = self
+            .face_detector
+            .as_ref()
+            .map(|detector| detector.name().to_string())
+            .unwrap_or_default();
+        for annotation in &face_annotations {
+            let bounds = face_region(annotation, width, height);
+            let confident = annotation
+                .detection_confidence
+                .is_none_or(|confidence| confidence >= self.min_face_confidence);
+            let mask = bounds.filter(|_| confident);
+            if let Some(region) = &mask {
+                self.face_mask_style.apply(img, region);
+            }
+
+            report.faces.push(FaceFinding {
+                bounds,
+                confidence: annotation.detection_confidence,
+                detector: face_detector_name.clone(),
+                mask,
+            });
+        }
+        report.timings.masking_ms = StageTimings::millis(started.elapsed());
+
+        Ok(report)
+    }
+
+    /// Read an image, mask it and save it to the output directory
+    ///
+    /// The output file is named after the input file with the configured
+    /// prefix (`masked_` by default).
+    ///
+    /// # Arguments
+    ///
+    /// * `input_path` - The path to the input image
+    /// * `output_dir` - The directory to save the output image
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Report>` - Everything that was detected and masked, with the output path
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If the image processing fails
+    ///
+    pub fn process_file(&self, input_path: &Path, output_dir: &Path) -> Result<Report> {
+        info!("Image processing started");
+        let file = self.read_file(input_path, output_dir)?;
+        let detections = self.detect(&file.img, Some(&file.bytes));
+        self.write_file(file, detections)
+    }
+
+    /// Mask several images and save them to the output directory
+    ///
+    /// When the Vision API is used, images are sent in batches of the
+    /// configured size (see [`AnonymizerBuilder::batch_size`]). A failure
+    /// affects only the image it belongs to.
+    ///
+    /// # Arguments
+    ///
+    /// * `input_paths` - The paths to the input images
+    /// * `output_dir` - The directory to save the output images
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Result<Report>>` - The outcome of each image, in the order of `input_paths`
+    ///
+    pub fn process_files(&self, input_paths: &[PathBuf], output_dir: &Path) -> Vec<Result<Report>> {
+        let mut results = Vec::with_capacity(input_paths.len());
+
+        for (batch_index, batch) in input_paths.chunks(self.batch_size).enumerate() {
+            info!(
+                "Processing batch {} ({} images)",
+                batch_index + 1,
+                batch.len()
+            );
+
+            let mut batch_results = Vec::with_capacity(batch.len());
+            let mut files = Vec::new();
+            for (index, input_path) in batch.iter().enumerate() {
+                match self.read_file(input_path, output_dir) {
+                    Ok(file) => files.push((index, file)),
+                    Err(e) => batch_results.push((index, Err(e))),
+                }
+            }
+
+            let images: Vec<_> = files
+                .iter()
+                .map(|(_, file)| (&file.img, file.bytes.as_slice()))
+                .collect();
+            let detections = self.detect_many(&images);
+
+            for ((index, file), detections) in files.into_iter().zip(detections) {
+                batch_results.push((index, self.write_file(file, detections)));
+            }
+
+            batch_results.sort_by_key(|(index, _)| *index);
+            results.extend(batch_results.into_iter().map(|(_, result)| result));
+        }
+
+        results
+    }
+
+    /// Mask every image of a directory and save them to the output directory
+    ///
+    /// Files are processed in name order; files without a known image
+    /// extension and subdirectories are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `input_dir` - The directory containing the input images
+    /// * `output_dir` - The directory to save the output images
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<(PathBuf, Result<Report>)>>` - Each input image with its outcome
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Io` - If the input directory cannot be read
+    ///
+    pub fn process_dir(
+        &self,
+        input_dir: &Path,
+        output_dir: &Path,
+    ) -> Result<Vec<(PathBuf, Result<Report>)>> {
+        let entries =
+            fs::read_dir(input_dir).map_err(|e| Error::io("Failed to read input directory", e))?;
+
+        let mut input_paths = Vec::new();
+        for entry in entries {
+            let path = entry
+                .map_err(|e| Error::io("Failed to read input directory", e))?
+                .path();
+            if path.is_file() && ImageFormat::from_path(&path).is_ok() {
+                input_paths.push(path);
+            }
+        }
+        input_paths.sort();
+        info!("Found {} images in {:?}", input_paths.len(), input_dir);
+
+        let results = self.process_files(&input_paths, output_dir);
+        Ok(input_paths.into_iter().zip(results).collect())
+    }
+
+    /// Read and decode an input image and work out where to save it
+    fn read_file(&self, input_path: &Path, output_dir: &Path) -> Result<InputFile> {
+        info!("Reading input image: {:?}", input_path);
+        let bytes = fs::read(input_path).map_err(|e| Error::io("Failed to open input image", e))?;
+
+        let file_name = input_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_else(|| Error::Config(format!("Invalid input filename: {:?}", input_path)))?;
+
+        let output_path = output_dir.join(format!("{}{}", self.output_prefix, file_name));
+
+        // The output format follows the file extension, like `DynamicImage::save`
+        let format = ImageFormat::from_path(&output_path)
+            .or_else(|_| image::guess_format(&bytes))
+            .map_err(Error::Decode)?;
+
+        let started = Instant::now();
+        let img = image::load_from_memory(&bytes).map_err(Error::Decode)?;
+        let decode_ms = StageTimings::millis(started.elapsed());
+
+        Ok(InputFile {
+            input_path: input_path.to_path_buf(),
+            output_path,
+            format,
+            bytes,
+            img,
+            decode_ms,
+        })
+    }
+
+    /// Mask a decoded input image and save it
+    fn write_file(&self, file: InputFile, detections: Result<Detections>) -> Result<Report> {
+        let InputFile {
+            input_path,
+            output_path,
+            format,
+            bytes,
+            mut img,
+            decode_ms,
+        } = file;
+
+        let (output, mut report) = self.mask_encoded(&mut img, &bytes, format, detections)?;
+        report.timings.decode_ms = decode_ms;
+
+        // Create output directory if it doesn't exist
+        if let Some(output_dir) = output_path.parent().filter(|dir| !dir.exists()) {
+            debug!("Creating output directory: {:?}", output_dir);
+            fs::create_dir_all(output_dir)
+                .map_err(|e| Error::io("Failed to create output directory", e))?;
+        }
+
+        fs::write(&output_path, output).map_err(|e| Error::io("Failed to save output image", e))?;
+
+        info!("Saved processed image to: {:?}", output_path);
+        report.input = Some(input_path);
+        report.output = Some(output_path);
+        Ok(report)
+    }
+
+    /// Decode, mask and re-encode an image, hashing input and output
+    fn anonymize_encoded(
+        &self,
+        bytes: &[u8],
+        output_format: ImageFormat,
+    ) -> Result<(Vec<u8>, Report)> {
+        let started = Instant::now();
+        let mut img = image::load_from_memory(bytes).map_err(Error::Decode)?;
+        let decode_ms = StageTimings::millis(started.elapsed());
+
+        let detections = self.detect(&img, Some(bytes));
+        let (output, mut report) = self.mask_encoded(&mut img, bytes, output_format, detections)?;
+        report.timings.decode_ms = decode_ms;
+        Ok((output, report))
+    }
+
+    /// Mask a decoded image and encode the result, hashing input and output
+    fn mask_encoded(
+        &self,
+        img: &mut DynamicImage,
+        bytes: &[u8],
+        output_format: ImageFormat,
+        detections: Result<Detections>,
+    ) -> Result<(Vec<u8>, Report)> {
+        let mut report = self.mask_detected(img, detections)?;
+
+        let started = Instant::now();
+        let mut output = Vec::new();
+        img.write_to(
+            &mut Cursor::new(&mut output),
+            ImageOutputFormat::from(output_format),
+        )
+        .map_err(Error::Encode)?;
+        report.timings.encode_ms = StageTimings::millis(started.elapsed());
+
+        report.input_sha256 = Some(sha256_hex(bytes));
+        report.output_sha256 = Some(sha256_hex(&output));
+        Ok((output, report))
+    }
+
+    /// Run face detection, when enabled
+    fn detect_faces(&self, img: &DynamicImage) -> Result<Vec<FaceAnnotation>> {
+        let Some(face_detector) = &self.face_detector else {
+            return Ok(Vec::new());
+        };
+
+        info!("Face detection enabled, detecting faces...");
+        face_detector.detect_faces(img)
+    }
+
+    /// Record a backend failure in the report
+    ///
+    /// Returns `Ok(true)` when the image must be blacked out, and the error
+    /// itself when processing must stop.
+    fn record_failure(&self, report: &mut Report, stage: Stage, error: Error) -> Result<bool> {
+        error!("{:?} failed: {}", stage, error);
+        match self.on_failure {
+            FailurePolicy::Abort => Err(error),
+            policy => {
+                report.failures.push(StageFailure {
+                    stage,
+                    error: error.to_string(),
+                });
+                Ok(policy == FailurePolicy::Blackout)
+            }
+        }
+    }
+}
+
+/// What to do when a detection or classification backend fails
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FailurePolicy {
+    /// Keep going with what is available and record the failure in the report
+    ///
+    /// Text detection failures still abort, since nothing could be masked.
+    #[default]
+    Lenient,
+    /// Abort processing with the error, no output is produced
+    Abort,
+    /// Black out the whole image and record the failure in the report
+    Blackout,
+}
+
+/// Cover the whole image with an opaque fill after a failure
+fn black_out(img: &mut DynamicImage, mut report: Report) -> Report {
+    let (width, height) = img.dimensions();
+    if width > 0 && height > 0 {
+        let region = MaskRegion {
+            min_x: 0,
+            min_y: 0,
+            max_x: width - 1,
+            max_y: height - 1,
+        };
+        MaskStyle::Solid([0, 0, 0, 255]).apply(img, &region);
+    }
+    info!("Blacked out the whole image after a backend failure");
+    report.blacked_out = true;
+    report
+}
+
+/// Text and faces found in an image, before masking
+struct Detections {
+    texts: Vec<TextAnnotation>,
+    /// Character boxes of each text annotation, when the detector reports them
+    symbols: Vec<Vec<SymbolBox>>,
+    /// Faces found by the Vision request, `None` when faces are detected separately
+    faces: Option<Vec<FaceAnnotation>>,
+    /// Time spent detecting, for a batched request the time of the whole batch
+    elapsed_ms: f64,
+}
+
+/// An input image read from disk, waiting to be masked
+struct InputFile {
+    input_path: PathBuf,
+    output_path: PathBuf,
+    format: ImageFormat,
+    bytes: Vec<u8>,
+    img: DynamicImage,
+    decode_ms: f64,
+}
+
+/// Encoded image sent to the Vision API
+///
+/// The original bytes are sent as is when the Vision API accepts their
+/// format, which avoids re-encoding the decoded image as PNG.
+fn vision_payload<'a>(img: &DynamicImage, encoded: Option<&'a [u8]>) -> Result<Cow<'a, [u8]>> {
+    let supported = |bytes: &[u8]| {
+        matches!(
+            image::guess_format(bytes),
+            Ok(ImageFormat::Png
+                | ImageFormat::Jpeg
+                | ImageFormat::Gif
+                | ImageFormat::Bmp
+                | ImageFormat::WebP
+                | ImageFormat::Ico
+                | ImageFormat::Tiff)
+        )
+    };
+
+    match encoded {
+        Some(bytes) if supported(bytes) => Ok(Cow::Borrowed(bytes)),
+        _ => Ok(Cow::Owned(crate::encode_png(img)?)),
+    }
+}
+
+/// Builder for [`Anonymizer`]
+///
+/// Backends that are not set explicitly default to the Google Cloud services,
+/// authenticated with the configured API key or the `GCP_API_KEY` environment
+/// variable.
+pub struct AnonymizerBuilder {
+    api_key: Option<String>,
+    gemini_model: Option<String>,
+    text_detector: Option<Box<dyn TextDetector>>,
+    face_detector: Option<Box<dyn FaceDetector>>,
+    mask_faces: bool,
+    classifier: Option<Box<dyn SensitivityClassifier>>,
+    criteria: SensitiveTextCriteria,
+    mask_list: MaskList,
+    text_mask_style: MaskStyle,
+    face_mask_style: MaskStyle,
+    max_text_box_ratio: f32,
+    min_face_confidence: f32,
+    output_prefix: String,
+    client: Option<ApiClient>,
+    batch_size: usize,
+    on_failure: FailurePolicy,
+    document_text: Option<TextGranularity>,
+    min_text_confidence: f32,
+}
+
+impl Default for AnonymizerBuilder {
+    fn default() -> Self {
+        Self {
+            api_key: None,
+            gemini_model: None,
+            text_detector: None,
+            face_detector: None,
+            mask_faces: false,
+            classifier: None,
+            criteria: SensitiveTextCriteria::default(),
+            mask_list: MaskList::default(),
+            text_mask_style: MaskStyle::TEXT_DEFAULT,
+            face_mask_style: MaskStyle::FACE_DEFAULT,
+            max_text_box_ratio: DEFAULT_MAX_BOX_RATIO,
+            min_face_confidence: 0.0,
+            output_prefix: "masked_".to_string(),
+            client: None,
+            batch_size: MAX_BATCH_SIZE,
+            on_failure: FailurePolicy::default(),
+            document_text: None,
+            min_text_confidence: 0.0,
+        }
+    }
+}
+
+impl AnonymizerBuilder {
+    /// API key for the default Google Cloud backends, instead of `GCP_API_KEY`
+    pub fn api_key(mut self, api_key: impl Into<String>) -> Self {
+        self.api_key = Some(api_key.into());
+        self
+    }
+
+    /// Gemini model for the default classifier, instead of `GEMINI_MODEL`
+    pub fn gemini_model(mut self, model: impl Into<String>) -> Self {
+        self.gemini_model = Some(model.into());
+        self
+    }
+
+    /// Detect text with the Vision `DOCUMENT_TEXT_DETECTION` feature
+    ///
+    /// Text is then classified and masked line by line or word by word. At
+    /// [`TextGranularity::Character`], text is classified by word and the
+    /// sensitive characters are masked one by one. Has no effect on a custom
+    /// text detector.
+    pub fn document_text(mut self, granularity: TextGranularity) -> Self {
+        self.document_text = Some(granularity);
+        self
+    }
+
+    /// Minimum recognition confidence (0.0 to 1.0) for a word found by
+    /// [`Self::document_text`] to be classified, less confident words are ignored
+    pub fn min_text_confidence(mut self, confidence: f32) -> Self {
+        self.min_text_confidence = confidence;
+        self
+    }
+
+    /// HTTP client used by the default Google Cloud backends
+    ///
+    /// Backends passed to the builder keep their own client.
+    pub fn client(mut self, client: ApiClient) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Backend used to find text
+    pub fn text_detector(mut self, detector: impl TextDetector + 'static) -> Self {
+        self.text_detector = Some(Box::new(detector));
+        self
+    }
+
+    /// Backend used to find faces, enabling face masking
+    pub fn face_detector(mut self, detector: impl FaceDetector + 'static) -> Self {
+        self.face_detector = Some(Box::new(detector));
+        self.mask_faces = true;
+        self
+    }
+
+    /// Enable or disable face masking
+    ///
+    /// Uses the Vision API unless a face detector is set.
+    pub fn mask_faces(mut self, enabled: bool) -> Self {
+        self.mask_faces = enabled;
+        self
+    }
+
+    /// Backend deciding which detected text is sensitive
+    pub fn classifier(mut self, classifier: impl SensitivityClassifier + 'static) -> Self {
+        self.classifier = Some(Box::new(classifier));
+        self
+    }
+
+    /// Kinds of sensitive text detected locally, before asking the classifier
+    ///
+    /// API keys and tokens (with the secret rules), random tokens, emails,
+    /// phone numbers, card numbers, names and company names are found without
+    /// any API call when enabled, all of them by default.
+    pub fn criteria(mut self, criteria: SensitiveTextCriteria) -> Self {
+        self.criteria = criteria;
+        self
+    }
+
+    /// Texts that are always masked when an annotation contains them,
+    /// ignoring case and Unicode compatibility forms. Can be called several
+    /// times.
+    pub fn mask_texts<I, S>(mut self, texts: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.mask_list = self.mask_list.with_texts(texts);
+        self
+    }
+
+    /// Only mask the texts of [`Self::mask_texts`] as whole words, so that
+    /// `Ann` does not mask `Annual`
+    pub fn mask_whole_words(mut self, enabled: bool) -> Self {
+        self.mask_list = self.mask_list.with_whole_words(enabled);
+        self
+    }
+
+    /// Regular expressions whose matches are always masked
+    ///
+    /// The name of the matching pattern is recorded in the verdict.
+    pub fn mask_patterns(mut self, patterns: impl IntoIterator<Item = MaskPattern>) -> Self {
+        self.mask_list = self.mask_list.with_patterns(patterns);
+        self
+    }
+
+    /// Salted digests of secrets that are always masked, see [`SecretHashes`]
+    pub fn secret_hashes(mut self, secrets: SecretHashes) -> Self {
+        self.mask_list = self.mask_list.with_secret_hashes(secrets);
+        self
+    }
+
+    /// Style used to hide sensitive text
+    pub fn text_mask_style(mut self, style: MaskStyle) -> Self {
+        self.text_mask_style = style;
+        self
+    }
+
+    /// Style used to hide faces
+    pub fn face_mask_style(mut self, style: MaskStyle) -> Self {
+        self.face_mask_style = style;
+        self
+    }
+
+    /// Maximum text box size relative to the image, larger boxes are not masked
+    pub fn max_text_box_ratio(mut self, ratio: f32) -> Self {
+        self.max_text_box_ratio = ratio;
+        self
+    }
+
+    /// Minimum detection confidence (0.0 to 1.0) for a face to be masked
+    pub fn min_face_confidence(mut self, confidence: f32) -> Self {
+        self.min_face_confidence = confidence;
+        self
+    }
+
+    /// Prefix added to the file name of saved images
+    pub fn output_prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.output_prefix = prefix.into();
+        self
+    }
+
+    /// Number of images sent in one Vision API request by [`Anonymizer::process_files`]
+    ///
+    /// Clamped between 1 and [`MAX_BATCH_SIZE`], the limit of the Vision API.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// What to do when a detection or classification backend fails
+    ///
+    /// With a policy other than [`FailurePolicy::Lenient`], the default
+    /// classifier uses Gemini alone, without falling back to the rules.
+    pub fn on_failure(mut self, policy: FailurePolicy) -> Self {
+        self.on_failure = policy;
+        self
+    }
+
+    /// Build the anonymizer
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Anonymizer>` - The configured anonymizer
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If a default Google Cloud backend is needed but no API key is available
+    ///
+    pub fn build(self) -> Result<Anonymizer> {
+        let api_key = self.api_key.or_else(|| env::var("GCP_API_KEY").ok());
+        let require_api_key = || api_key.clone().ok_or_else(Error::missing_api_key);
+        let client = self.client.unwrap_or_else(ApiClient::shared);
+
+        // Text, and faces when both use Vision, are detected with a single
+        // request through a shared Vision client
+        let vision = match &self.text_detector {
+            Some(_) => None,
+            None => Some(VisionClient::new(require_api_key()?).with_client(client.clone())),
+        };
+        let vision_faces = vision.is_some() && self.mask_faces && self.face_detector.is_none();
+
+        let text_detector = match self.text_detector {
+            Some(detector) => detector,
+            None => {
+                let mut detector =
+                    VisionTextDetector::new(require_api_key()?).with_client(client.clone());
+                if let Some(granularity) = self.document_text {
+                    detector = detector.document(granularity);
+                }
+                Box::new(detector)
+            }
+        };
+
+        let face_detector = match (self.face_detector, self.mask_faces) {
+            (Some(detector), true) => Some(detector),
+            (None, true) => Some(Box::new(
+                VisionFaceDetector::new(require_api_key()?).with_client(client.clone()),
+            ) as _),
+            (_, false) => None,
+        };
+
+        let classifier = match (self.classifier, &api_key) {
+            (Some(classifier), _) => classifier,
+            (None, Some(api_key)) => {
+                let model = self
+                    .gemini_model
+                    .or_else(|| env::var("GEMINI_MODEL").ok())
+                    .unwrap_or_else(|| DEFAULT_GEMINI_MODEL.to_string());
+                let gemini = GeminiClassifier::new(api_key.clone(), model).with_client(client);
+                // Falling back to the rules would hide Gemini failures from a strict policy
+                if self.on_failure == FailurePolicy::Lenient {
+                    Box::new(ChainClassifier::new(vec![
+                        Box::new(gemini),
+                        Box::new(RuleBasedClassifier::default()),
+                    ])) as Box<dyn SensitivityClassifier>
+                } else {
+                    Box::new(gemini)
+                }
+            }
+            (None, None) => Box::new(RuleBasedClassifier::default()),
+        };
+
+        Ok(Anonymizer {
+            text_detector,
+            face_detector,
+            vision,
+            vision_faces,
+            document_text: self.document_text,
+            min_text_confidence: self.min_text_confidence,
+            batch_size: self.batch_size.clamp(1, MAX_BATCH_SIZE),
+            on_failure: self.on_failure,
+            classifier,
+            criteria: self.criteria,
+            mask_list: self.mask_list,
+            text_mask_style: self.text_mask_style,
+            face_mask_style: self.face_mask_style,
+            max_text_box_ratio: self.max_text_box_ratio,
+            min_face_confidence: self.min_face_confidence,
+            output_prefix: self.output_prefix,
+        })
+    }
+}
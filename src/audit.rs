@@ -0,0 +1,224 @@
+use serde::Serialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::error::{Error, Result};
+use crate::mask::MaskRegion;
+use crate::ocr::entropy::ENTROPY_SOURCE;
+use crate::ocr::masking::{
+    ADDITIONAL_MASKS_SOURCE, HASHED_SECRETS_SOURCE, LABELED_VALUES_SOURCE, MASK_PATTERNS_SOURCE,
+    TABLE_COLUMNS_SOURCE,
+};
+use crate::ocr::pii::PII_DETECTORS_SOURCE;
+use crate::ocr::secrets::SECRET_RULES_SOURCE;
+use crate::report::{Report, StageFailure, sha256_hex};
+
+/// Why a region was masked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaskCategory {
+    /// The text contained one of the user supplied mask texts
+    AdditionalMask,
+    /// The text matched one of the user supplied mask patterns
+    MaskPattern,
+    /// The text contained one of the user supplied hashed secrets
+    HashedSecret,
+    /// A secret rule matched an API key, token or credential
+    SecretRule,
+    /// The text contained a token random enough to be a secret
+    HighEntropy,
+    /// The text was the value of a known form label
+    LabeledValue,
+    /// The text was a cell under a known table header
+    TableColumn,
+    /// A local detector found an email, phone number, card number or name
+    Pii,
+    /// The Gemini classifier flagged the text
+    Gemini,
+    /// The offline rule-based classifier flagged the text
+    Rules,
+    /// Another classifier flagged the text
+    Classifier,
+    /// The region contained a face
+    Face,
+}
+
+impl MaskCategory {
+    /// Category of a text mask, based on the source recorded in its verdict
+    pub fn from_source(source: &str) -> Self {
+        match source {
+            ADDITIONAL_MASKS_SOURCE => Self::AdditionalMask,
+            MASK_PATTERNS_SOURCE => Self::MaskPattern,
+            HASHED_SECRETS_SOURCE => Self::HashedSecret,
+            SECRET_RULES_SOURCE => Self::SecretRule,
+            ENTROPY_SOURCE => Self::HighEntropy,
+            LABELED_VALUES_SOURCE => Self::LabeledValue,
+            TABLE_COLUMNS_SOURCE => Self::TableColumn,
+            PII_DETECTORS_SOURCE => Self::Pii,
+            "gemini" => Self::Gemini,
+            "rules" => Self::Rules,
+            _ => Self::Classifier,
+        }
+    }
+}
+
+/// A masked region as recorded in the audit log
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRegion {
+    pub category: MaskCategory,
+    /// The backend that made the masking decision
+    pub backend: String,
+    pub region: MaskRegion,
+    /// The masked text and the decision reason, only when explicitly requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+/// Audit evidence for one processed image
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditRecord {
+    pub input: Option<PathBuf>,
+    pub output: Option<PathBuf>,
+    pub input_sha256: Option<String>,
+    pub output_sha256: Option<String>,
+    /// False when a backend failed, so that `regions` may be incomplete
+    pub complete: bool,
+    /// Whether the whole image was blacked out because of a failure
+    pub blacked_out: bool,
+    pub failures: Vec<StageFailure>,
+    pub regions: Vec<AuditRegion>,
+    /// Why the image could not be processed at all, no output was written
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl AuditRecord {
+    /// Build the audit record of a processing report
+    ///
+    /// The raw text and the decision reason (which may quote the matched
+    /// text) are left out unless `include_text` is set.
+    ///
+    /// # Arguments
+    ///
+    /// * `report` - The report of the processed image
+    /// * `include_text` - Whether to include the masked text in the record
+    ///
+    /// # Returns
+    ///
+    /// * `AuditRecord` - The audit record
+    ///
+    pub fn from_report(report: &Report, include_text: bool) -> Self {
+        let text_regions = report.texts.iter().flat_map(|finding| {
+            let whole = finding.mask.filter(|_| finding.masked_parts.is_empty());
+            let parts = finding
+                .masked_parts
+                .iter()
+                .map(|part| (part.region, finding.text.get(part.range.clone())));
+            whole
+                .map(|region| (region, Some(finding.text.as_str())))
+                .into_iter()
+                .chain(parts)
+                .filter_map(|(region, text)| {
+                    let verdict = finding.verdict.as_ref()?;
+                    Some(AuditRegion {
+                        category: MaskCategory::from_source(&verdict.classifier),
+                        backend: verdict.classifier.clone(),
+                        region,
+                        text: include_text.then(|| text.unwrap_or_default().to_string()),
+                        reason: include_text.then(|| verdict.reason.clone()),
+                    })
+                })
+        });
+
+        let face_regions = report.faces.iter().filter_map(|finding| {
+            Some(AuditRegion {
+                category: MaskCategory::Face,
+                backend: finding.detector.clone(),
+                region: finding.mask?,
+                text: None,
+                reason: None,
+            })
+        });
+
+        Self {
+            input: report.input.clone(),
+            output: report.output.clone(),
+            input_sha256: report.input_sha256.clone(),
+            output_sha256: report.output_sha256.clone(),
+            complete: report.is_complete(),
+            blacked_out: report.blacked_out,
+            failures: report.failures.clone(),
+            regions: text_regions.chain(face_regions).collect(),
+            error: None,
+        }
+    }
+
+    /// Build the audit record of an image that could not be processed
+    ///
+    /// The input is hashed when it can still be read, so that the record
+    /// identifies which file was left unredacted.
+    ///
+    /// # Arguments
+    ///
+    /// * `input` - The path of the image
+    /// * `error` - The error that stopped processing
+    ///
+    /// # Returns
+    ///
+    /// * `AuditRecord` - The audit record, marked as incomplete
+    ///
+    pub fn from_failure(input: &Path, error: &Error) -> Self {
+        Self {
+            input: Some(input.to_path_buf()),
+            output: None,
+            input_sha256: fs::read(input).ok().map(|bytes| sha256_hex(&bytes)),
+            output_sha256: None,
+            complete: false,
+            blacked_out: false,
+            failures: Vec::new(),
+            regions: Vec::new(),
+            error: Some(error.to_string()),
+        }
+    }
+}
+
+/// Audit log covering every image of a run
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditLog {
+    pub tool: String,
+    pub version: String,
+    /// Seconds since the Unix epoch when the log was created
+    pub created_at: u64,
+    pub records: Vec<AuditRecord>,
+}
+
+impl Default for AuditLog {
+    fn default() -> Self {
+        Self {
+            tool: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+            records: Vec::new(),
+        }
+    }
+}
+
+impl AuditLog {
+    /// Write the log as pretty-printed JSON
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Io` - If the file cannot be written
+    ///
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)
+            .map_err(|e| Error::io("Failed to serialize audit log", e.into()))?;
+        fs::write(path, json).map_err(|e| Error::io("Failed to write audit log", e))
+    }
+}
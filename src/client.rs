@@ -0,0 +1,263 @@
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::{Certificate, Proxy};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::Duration;
+use tracing::{debug, warn};
+
+use crate::error::{Error, Result, check_status};
+
+/// Default base URL of the Google Cloud Vision API
+pub const DEFAULT_VISION_BASE_URL: &str = "https://vision.googleapis.com";
+
+/// Default base URL of the Google Gemini API
+pub const DEFAULT_GEMINI_BASE_URL: &str = "https://generativelanguage.googleapis.com";
+
+/// Header carrying the API key, which keeps the key out of request URLs
+pub(crate) const API_KEY_HEADER: &str = "x-goog-api-key";
+
+/// Settings of the HTTP client shared by the Google Cloud backends
+#[derive(Debug, Clone)]
+pub struct ClientConfig {
+    /// Base URL of the Vision API, e.g. a regional endpoint or a local mock server
+    pub vision_base_url: String,
+    /// Base URL of the Gemini API
+    pub gemini_base_url: String,
+    /// Maximum time to establish a connection, `None` to wait indefinitely
+    pub connect_timeout: Option<Duration>,
+    /// Maximum time for a whole request, `None` to wait indefinitely
+    pub timeout: Option<Duration>,
+    /// Proxy URL used for every request, instead of the `HTTPS_PROXY` environment variables
+    pub proxy: Option<String>,
+    /// Additional PEM encoded root certificates to trust
+    pub ca_certs: Vec<PathBuf>,
+    /// How failed requests are retried
+    pub retry: RetryPolicy,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            vision_base_url: DEFAULT_VISION_BASE_URL.to_string(),
+            gemini_base_url: DEFAULT_GEMINI_BASE_URL.to_string(),
+            connect_timeout: Some(Duration::from_secs(10)),
+            timeout: Some(Duration::from_secs(60)),
+            proxy: None,
+            ca_certs: Vec::new(),
+            retry: RetryPolicy::default(),
+        }
+    }
+}
+
+/// How requests failing with a transient error are retried
+///
+/// The delay before each retry grows exponentially from `initial_backoff` up
+/// to `max_backoff`, and is randomized by up to `jitter` (a fraction of the
+/// delay) so that concurrent callers do not retry in lockstep. A `Retry-After`
+/// delay sent by the server replaces the computed delay, capped at
+/// `max_backoff`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts, including the first one
+    pub max_attempts: u32,
+    /// Delay before the first retry
+    pub initial_backoff: Duration,
+    /// Upper bound of any delay between attempts
+    pub max_backoff: Duration,
+    /// Factor applied to the delay after each retry
+    pub multiplier: f64,
+    /// Random variation of each delay, as a fraction of the delay (0.0 to 1.0)
+    pub jitter: f64,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            multiplier: 2.0,
+            jitter: 0.2,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Policy that never retries
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Self::default()
+        }
+    }
+
+    /// Delay before the given retry
+    ///
+    /// # Arguments
+    ///
+    /// * `retry` - The retry number, starting at 1
+    /// * `error` - The error of the failed attempt
+    ///
+    /// # Returns
+    ///
+    /// * `Duration` - The time to wait before sending the request again
+    ///
+    pub fn delay(&self, retry: u32, error: &Error) -> Duration {
+        if let Some(retry_after) = error.retry_after() {
+            return retry_after.min(self.max_backoff);
+        }
+
+        let exponent = retry.saturating_sub(1).min(30) as i32;
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(exponent);
+        let jitter = self.jitter.clamp(0.0, 1.0) * (2.0 * fastrand::f64() - 1.0);
+        let delay = (backoff * (1.0 + jitter)).min(self.max_backoff.as_secs_f64());
+        Duration::from_secs_f64(delay.max(0.0))
+    }
+}
+
+/// HTTP client for the Google Cloud APIs
+///
+/// Cloning is cheap and clones share the same connection pool, so one client
+/// can be handed to every backend.
+#[derive(Debug, Clone)]
+pub struct ApiClient {
+    http: Client,
+    config: ClientConfig,
+}
+
+impl ApiClient {
+    /// Create a client with the given settings
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - The client settings
+    ///
+    /// # Returns
+    ///
+    /// * `Result<ApiClient>` - The configured client
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Io` - If a CA certificate file cannot be read
+    /// * `Error::Config` - If the proxy URL or a certificate is invalid
+    ///
+    pub fn new(config: ClientConfig) -> Result<Self> {
+        let mut builder = Client::builder();
+
+        if let Some(timeout) = config.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        // The blocking client applies a 30 second timeout unless told otherwise
+        builder = builder.timeout(config.timeout);
+
+        if let Some(proxy) = &config.proxy {
+            let proxy = Proxy::all(proxy)
+                .map_err(|e| Error::Config(format!("Invalid proxy URL '{}': {}", proxy, e)))?;
+            builder = builder.proxy(proxy);
+        }
+
+        for path in &config.ca_certs {
+            let pem = fs::read(path)
+                .map_err(|e| Error::io(format!("Failed to read CA certificate {:?}", path), e))?;
+            let certificate = Certificate::from_pem(&pem)
+                .map_err(|e| Error::Config(format!("Invalid CA certificate {:?}: {}", path, e)))?;
+            builder = builder.add_root_certificate(certificate);
+        }
+
+        let http = builder
+            .build()
+            .map_err(|e| Error::Config(format!("Failed to build HTTP client: {}", e)))?;
+        debug!("Created API client with {:?}", config);
+
+        Ok(Self { http, config })
+    }
+
+    /// Client with the default settings, shared by every caller
+    ///
+    /// Backends created without an explicit client use this one, so that
+    /// repeated calls reuse the same connections.
+    pub fn shared() -> Self {
+        static SHARED: OnceLock<ApiClient> = OnceLock::new();
+        SHARED
+            .get_or_init(|| {
+                Self::new(ClientConfig::default()).expect("Failed to build default HTTP client")
+            })
+            .clone()
+    }
+
+    /// The settings of this client
+    pub fn config(&self) -> &ClientConfig {
+        &self.config
+    }
+
+    /// The underlying HTTP client
+    pub(crate) fn http(&self) -> &Client {
+        &self.http
+    }
+
+    /// Send a request, retrying transient failures according to the retry policy
+    ///
+    /// # Arguments
+    ///
+    /// * `api` - Name of the API, used in error messages
+    /// * `request` - The request to send
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Response>` - The first successful response
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - The error of the last attempt, or the first non-transient error
+    ///
+    pub(crate) fn send(&self, api: &str, request: RequestBuilder) -> Result<Response> {
+        let policy = &self.config.retry;
+        let mut attempt = 1;
+        loop {
+            // Requests with a streaming body cannot be cloned and are sent only once
+            let Some(current) = request.try_clone() else {
+                return send_once(api, request);
+            };
+
+            match send_once(api, current) {
+                Err(err) if err.is_transient() && attempt < policy.max_attempts => {
+                    let delay = policy.delay(attempt, &err);
+                    warn!(
+                        "{} (attempt {}/{}), retrying in {:?}",
+                        err, attempt, policy.max_attempts, delay
+                    );
+                    thread::sleep(delay);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Full URL of a Vision API method, e.g. `v1/images:annotate`
+    pub(crate) fn vision_url(&self, path: &str) -> String {
+        join_url(&self.config.vision_base_url, path)
+    }
+
+    /// Full URL of a Gemini API method
+    pub(crate) fn gemini_url(&self, path: &str) -> String {
+        join_url(&self.config.gemini_base_url, path)
+    }
+}
+
+/// Send a request once and check its status
+fn send_once(api: &str, request: RequestBuilder) -> Result<Response> {
+    let response = request.send().map_err(|e| Error::http(api, e))?;
+    check_status(api, response)
+}
+
+/// Join a base URL and a path with exactly one slash between them
+fn join_url(base: &str, path: &str) -> String {
+    format!(
+        "{}/{}",
+        base.trim_end_matches('/'),
+        path.trim_start_matches('/')
+    )
+}
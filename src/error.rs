@@ -0,0 +1,212 @@
+use reqwest::StatusCode;
+use reqwest::blocking::Response;
+use std::io;
+use std::time::{Duration, SystemTime};
+use tracing::error;
+
+/// Errors returned by the library
+///
+/// The variants separate failures a caller may want to handle differently:
+/// bad input images, missing or rejected credentials, quota exhaustion and
+/// other API failures.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// Reading or writing a file failed
+    #[error("{context}: {source}")]
+    Io {
+        context: String,
+        #[source]
+        source: io::Error,
+    },
+
+    /// The input is not a valid image
+    #[error("Failed to decode image: {0}")]
+    Decode(#[source] image::ImageError),
+
+    /// The masked image could not be encoded
+    #[error("Failed to encode image: {0}")]
+    Encode(#[source] image::ImageError),
+
+    /// Credentials are missing or were rejected by the API
+    #[error("Authentication failed: {0}")]
+    Auth(String),
+
+    /// The API rejected the request because of rate limits or quota
+    #[error("Rate limited by {api}")]
+    RateLimited {
+        api: String,
+        /// Delay requested by the server through the `Retry-After` header
+        retry_after: Option<Duration>,
+    },
+
+    /// The API answered with an unexpected HTTP status
+    #[error("{api} request failed with status {status}: {body}")]
+    ApiError {
+        api: String,
+        status: u16,
+        body: String,
+        /// Delay requested by the server through the `Retry-After` header, sent with 503
+        retry_after: Option<Duration>,
+    },
+
+    /// The request could not be sent or the response could not be read
+    #[error("Failed to reach {api}: {source}")]
+    Http {
+        api: String,
+        #[source]
+        source: reqwest::Error,
+    },
+
+    /// The API could not process one image, the other images of a batch may have succeeded
+    #[error("{api} rejected the image (code {code}): {message}")]
+    ImageRejected {
+        api: String,
+        /// gRPC status code reported for the image
+        code: i32,
+        message: String,
+    },
+
+    /// The API answered with a body that could not be understood
+    #[error("Malformed response from {api}: {message}")]
+    MalformedResponse { api: String, message: String },
+
+    /// The configuration is invalid (bad pattern, bad option value, ...)
+    #[error("Invalid configuration: {0}")]
+    Config(String),
+}
+
+/// Result type used throughout the library
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// Build an I/O error with a description of the failed operation
+    pub fn io(context: impl Into<String>, source: io::Error) -> Self {
+        Self::Io {
+            context: context.into(),
+            source,
+        }
+    }
+
+    /// Build a transport error for the given API
+    ///
+    /// The request URL is dropped from the source error, so that a URL
+    /// carrying credentials never reaches logs or reports.
+    pub fn http(api: impl Into<String>, source: reqwest::Error) -> Self {
+        Self::Http {
+            api: api.into(),
+            source: source.without_url(),
+        }
+    }
+
+    /// Build a malformed response error for the given API
+    pub fn malformed(api: impl Into<String>, message: impl Into<String>) -> Self {
+        Self::MalformedResponse {
+            api: api.into(),
+            message: message.into(),
+        }
+    }
+
+    /// Error for a missing `GCP_API_KEY` environment variable
+    pub fn missing_api_key() -> Self {
+        Self::Auth("GCP_API_KEY environment variable not set".to_string())
+    }
+
+    /// Whether sending the same request again may succeed
+    ///
+    /// Rate limits, server side failures (500, 502, 503, 504), timeouts and
+    /// connection failures are transient. Bad credentials, bad requests and
+    /// malformed responses are not.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::RateLimited { .. } => true,
+            Self::ApiError { status, .. } => matches!(status, 500 | 502 | 503 | 504),
+            Self::Http { source, .. } => source.is_timeout() || source.is_connect(),
+            _ => false,
+        }
+    }
+
+    /// Delay requested by the server before retrying, if any
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited { retry_after, .. } | Self::ApiError { retry_after, .. } => {
+                *retry_after
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Turn a non-success HTTP response into the matching error
+///
+/// # Arguments
+///
+/// * `api` - Name of the API, used in error messages
+/// * `response` - The HTTP response
+///
+/// # Returns
+///
+/// * `Result<Response>` - The response itself if its status is a success
+///
+/// # Errors
+///
+/// * `Error::Auth` - For 401 and 403 responses, and rejected API keys
+/// * `Error::RateLimited` - For 429 responses
+/// * `Error::ApiError` - For any other non-success status, with the `Retry-After` delay if sent
+///
+pub(crate) fn check_status(api: &str, response: Response) -> Result<Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let retry_after = response
+        .headers()
+        .get(reqwest::header::RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_retry_after(value, SystemTime::now()));
+    let body = response
+        .text()
+        .unwrap_or_else(|_| "Could not read error response".to_string());
+
+    error!("{} request failed with status {}: {}", api, status, body);
+
+    Err(match status {
+        StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN => Error::Auth(body),
+        // Google APIs report invalid keys as 400 INVALID_ARGUMENT
+        StatusCode::BAD_REQUEST if body.contains("API_KEY_INVALID") => Error::Auth(body),
+        StatusCode::TOO_MANY_REQUESTS => Error::RateLimited {
+            api: api.to_string(),
+            retry_after,
+        },
+        _ => Error::ApiError {
+            api: api.to_string(),
+            status: status.as_u16(),
+            body,
+            retry_after,
+        },
+    })
+}
+
+/// Parse a `Retry-After` header value
+///
+/// Both forms of RFC 9110 are accepted: a number of seconds, and an HTTP
+/// date, which is turned into the delay from `now`. A date in the past
+/// means no delay.
+///
+/// # Arguments
+///
+/// * `value` - The header value
+/// * `now` - The current time
+///
+/// # Returns
+///
+/// * `Option<Duration>` - The requested delay, `None` if the value is invalid
+///
+pub fn parse_retry_after(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = httpdate::parse_http_date(value).ok()?;
+    Some(date.duration_since(now).unwrap_or(Duration::ZERO))
+}
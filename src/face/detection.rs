@@ -1,10 +1,12 @@
-use anyhow::{Context, Result};
-use base64::{Engine as _, engine::general_purpose};
-use reqwest::blocking::Client;
-use serde::{Deserialize, Serialize};
+use image::DynamicImage;
+use serde::Deserialize;
 use std::env;
 use std::path::Path;
-use tracing::{debug, error};
+use tracing::debug;
+
+use crate::client::ApiClient;
+use crate::error::{Error, Result};
+use crate::vision::{VisionClient, VisionFeature};
 
 #[derive(Debug, Deserialize)]
 pub struct FaceDetectionResponse {
@@ -57,27 +59,92 @@ pub struct Vertex {
     pub y: i32,
 }
 
-#[derive(Debug, Serialize)]
-struct FaceDetectionRequest {
-    requests: Vec<Request>,
+/// A backend that finds faces in an image
+pub trait FaceDetector: Send + Sync {
+    /// Short identifier of the detector, used in logs and reports
+    fn name(&self) -> &str {
+        "custom"
+    }
+
+    /// Detect faces in an in-memory image
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - The image to analyze
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<FaceAnnotation>>` - The detected face annotations
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If the detection fails
+    ///
+    fn detect_faces(&self, image: &DynamicImage) -> Result<Vec<FaceAnnotation>>;
 }
 
-#[derive(Debug, Serialize)]
-struct Request {
-    image: Image,
-    features: Vec<Feature>,
+/// Face detector backed by the Google Cloud Vision API
+#[derive(Debug, Clone)]
+pub struct VisionFaceDetector {
+    vision: VisionClient,
 }
 
-#[derive(Debug, Serialize)]
-struct Image {
-    content: String,
+impl VisionFaceDetector {
+    /// Create a detector that authenticates with the given API key
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            vision: VisionClient::new(api_key),
+        }
+    }
+
+    /// Send requests through the given client instead of the shared default one
+    pub fn with_client(mut self, client: ApiClient) -> Self {
+        self.vision = self.vision.with_client(client);
+        self
+    }
+
+    /// Create a detector using the `GCP_API_KEY` environment variable
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If `GCP_API_KEY` is not set
+    ///
+    pub fn from_env() -> Result<Self> {
+        let api_key = env::var("GCP_API_KEY").map_err(|_| Error::missing_api_key())?;
+        Ok(Self::new(api_key))
+    }
+
+    /// Detect faces in an encoded image (PNG, JPEG, ...)
+    ///
+    /// # Arguments
+    ///
+    /// * `image_data` - The encoded image bytes
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<FaceAnnotation>>` - The detected face annotations
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If the request fails or the response cannot be parsed
+    ///
+    pub fn detect_bytes(&self, image_data: &[u8]) -> Result<Vec<FaceAnnotation>> {
+        let response = self
+            .vision
+            .annotate(image_data, &[VisionFeature::FaceDetection])?;
+        Ok(response.face_annotations)
+    }
 }
 
-#[derive(Debug, Serialize)]
-struct Feature {
-    #[serde(rename = "type")]
-    feature_type: String,
-    max_results: i32,
+impl FaceDetector for VisionFaceDetector {
+    fn name(&self) -> &str {
+        "vision"
+    }
+
+    fn detect_faces(&self, image: &DynamicImage) -> Result<Vec<FaceAnnotation>> {
+        let image_data = crate::encode_png(image)?;
+        self.detect_bytes(&image_data)
+    }
 }
 
 /// Detect faces in an image using the Google Cloud Vision API
@@ -92,59 +159,13 @@ struct Feature {
 ///
 /// # Errors
 ///
-/// * `anyhow::Error` - If the image processing fails
+/// * `Error` - If the image processing fails
 ///
 pub fn detect_faces_with_api(image_path: &Path) -> Result<Vec<FaceAnnotation>> {
-    let api_key = env::var("GCP_API_KEY").context("GCP_API_KEY environment variable not set")?;
+    let detector = VisionFaceDetector::from_env()?;
     debug!("image_path: {}", image_path.display());
 
-    let image_data = std::fs::read(image_path).context("Failed to read image file")?;
-    let base64_image = general_purpose::STANDARD.encode(&image_data);
-
-    let request = FaceDetectionRequest {
-        requests: vec![Request {
-            image: Image {
-                content: base64_image,
-            },
-            features: vec![Feature {
-                feature_type: "FACE_DETECTION".to_string(),
-                max_results: 100,
-            }],
-        }],
-    };
-
-    let client = Client::new();
-    let response = client
-        .post(&format!(
-            "https://vision.googleapis.com/v1/images:annotate?key={}",
-            api_key
-        ))
-        .json(&request)
-        .send()
-        .context("Failed to send request to Google Cloud Vision API")?;
-
-    let response_text = response.text().context("Failed to get response text")?;
-
-    if response_text.len() > 1000 {
-        debug!(
-            "Response text (first 1000 chars): {}",
-            &response_text[..1000]
-        );
-        debug!("Response text length: {}", response_text.len());
-    } else {
-        debug!("Response text: {}", &response_text);
-    }
-
-    let response_body: FaceDetectionResponse = serde_json::from_str(&response_text)
-        .context("Failed to parse Google Cloud Vision API response")?;
-
-    if response_body.responses.is_empty() {
-        error!("No responses from Google Cloud Vision API");
-        anyhow::bail!("No responses from Google Cloud Vision API");
-    }
-
-    let annotations = response_body.responses[0].face_annotations.clone();
-    debug!("Detected {} face annotations", annotations.len());
-
-    Ok(annotations)
+    let image_data =
+        std::fs::read(image_path).map_err(|e| Error::io("Failed to read image file", e))?;
+    detector.detect_bytes(&image_data)
 }
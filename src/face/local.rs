@@ -0,0 +1,144 @@
+use std::sync::OnceLock;
+
+use image::{DynamicImage, GenericImageView, imageops::FilterType};
+use rustface::{ImageData, Model};
+use tracing::debug;
+
+use super::detection::{BoundingPoly, FaceAnnotation, FaceDetector, Vertex};
+use crate::error::Result;
+
+/// SeetaFace frontal face model, bundled so that no model file has to be installed
+///
+/// Distributed under the BSD 2-Clause License, see `models/LICENSE-seetaface`.
+const SEETAFACE_MODEL: &[u8] = include_bytes!("../../models/seeta_fd_frontal_v1.0.bin");
+
+/// Smallest face size, in pixels, the SeetaFace cascade can detect
+const MIN_WINDOW_SIZE: u32 = 20;
+
+/// Offline face detector based on the SeetaFace funnel-structured cascade
+///
+/// The bundled SeetaFace frontal model is run with a sliding window over an
+/// image pyramid of the grayscale image. No network access or model files
+/// are needed. Frontal and slightly turned faces are found; profiles and
+/// faces smaller than `min_face_size` pixels are not.
+#[derive(Debug, Clone)]
+pub struct LocalFaceDetector {
+    /// Images are downscaled so that their longest side is at most this many pixels
+    pub working_size: u32,
+    /// Minimum face size in pixels of the downscaled image, never below 20
+    pub min_face_size: u32,
+    /// Minimum cascade score for a window to be reported as a face
+    ///
+    /// Typical values are 0.95, 2.0 and 4.5: lower values find more faces
+    /// at the cost of more false positives.
+    pub score_threshold: f64,
+    /// Factor between two adjacent scales of the image pyramid, in (0.1, 0.99)
+    pub pyramid_scale_factor: f32,
+    /// Step in pixels of the sliding window, horizontally and vertically
+    pub window_step: u32,
+}
+
+impl Default for LocalFaceDetector {
+    /// Default settings for the local face detector
+    ///
+    /// # Returns
+    ///
+    /// * `LocalFaceDetector` - The default detector
+    ///
+    fn default() -> Self {
+        Self {
+            working_size: 1024,
+            min_face_size: MIN_WINDOW_SIZE,
+            score_threshold: 2.0,
+            pyramid_scale_factor: 0.8,
+            window_step: 4,
+        }
+    }
+}
+
+impl FaceDetector for LocalFaceDetector {
+    fn name(&self) -> &str {
+        "local"
+    }
+
+    fn detect_faces(&self, image: &DynamicImage) -> Result<Vec<FaceAnnotation>> {
+        let (width, height) = image.dimensions();
+        if width == 0 || height == 0 {
+            return Ok(Vec::new());
+        }
+
+        let longest = width.max(height);
+        let scale = if longest > self.working_size {
+            self.working_size as f32 / longest as f32
+        } else {
+            1.0
+        };
+
+        let gray = if scale < 1.0 {
+            let w = ((width as f32 * scale) as u32).max(1);
+            let h = ((height as f32 * scale) as u32).max(1);
+            image.resize_exact(w, h, FilterType::Triangle).to_luma8()
+        } else {
+            image.to_luma8()
+        };
+
+        // The cascade cannot look at anything smaller than its window
+        let min_face_size = self.min_face_size.max(MIN_WINDOW_SIZE);
+        if gray.width() < min_face_size || gray.height() < min_face_size {
+            return Ok(Vec::new());
+        }
+
+        let mut detector = rustface::create_detector_with_model(seetaface_model().clone());
+        detector.set_min_face_size(min_face_size);
+        detector.set_score_thresh(self.score_threshold);
+        detector.set_pyramid_scale_factor(self.pyramid_scale_factor);
+        detector.set_slide_window_step(self.window_step, self.window_step);
+
+        let faces = detector.detect(&ImageData::new(&gray, gray.width(), gray.height()));
+
+        let to_original =
+            |v: i32, limit: u32| ((v as f32 / scale).round() as i32).clamp(0, limit as i32);
+        let annotations: Vec<FaceAnnotation> = faces
+            .iter()
+            .map(|face| {
+                let bbox = face.bbox();
+                let min_x = to_original(bbox.x(), width);
+                let min_y = to_original(bbox.y(), height);
+                let max_x = to_original(bbox.x() + bbox.width() as i32, width);
+                let max_y = to_original(bbox.y() + bbox.height() as i32, height);
+
+                FaceAnnotation {
+                    bounding_poly: Some(BoundingPoly {
+                        vertices: vec![
+                            Vertex { x: min_x, y: min_y },
+                            Vertex { x: max_x, y: min_y },
+                            Vertex { x: max_x, y: max_y },
+                            Vertex { x: min_x, y: max_y },
+                        ],
+                    }),
+                    landmarks: None,
+                    detection_confidence: Some(confidence(face.score())),
+                }
+            })
+            .collect();
+
+        debug!("Detected {} faces locally", annotations.len());
+        Ok(annotations)
+    }
+}
+
+/// Parse the bundled SeetaFace model once
+fn seetaface_model() -> &'static Model {
+    static MODEL: OnceLock<Model> = OnceLock::new();
+    MODEL.get_or_init(|| {
+        rustface::read_model(SEETAFACE_MODEL).expect("bundled face model must load")
+    })
+}
+
+/// Map an unbounded cascade score to a confidence between 0.0 and 1.0
+///
+/// A score of 2.0, the default threshold, maps to 0.5.
+fn confidence(score: f64) -> f32 {
+    let score = score.max(0.0);
+    (score / (score + 2.0)) as f32
+}
@@ -1,2 +1,4 @@
 pub mod detection;
+#[cfg(feature = "local-faces")]
+pub mod local;
 pub mod masking;
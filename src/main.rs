@@ -1,17 +1,32 @@
-pub mod ocr;
-
 use anyhow::{Context, Result};
-use clap::Parser;
-use image_anonymizer::process_image;
-use serde::{Deserialize, Serialize};
+use clap::{Parser, ValueEnum};
+use image_anonymizer::audit::{AuditLog, AuditRecord};
+use image_anonymizer::client::{DEFAULT_GEMINI_BASE_URL, DEFAULT_VISION_BASE_URL};
+#[cfg(feature = "local-faces")]
+use image_anonymizer::face::local::LocalFaceDetector;
+use image_anonymizer::ocr::classifier::RuleBasedClassifier;
+use image_anonymizer::ocr::document::TextGranularity;
+use image_anonymizer::ocr::entropy::EntropyThresholds;
+use image_anonymizer::ocr::gemini::{DEFAULT_GEMINI_MODEL, GeminiClassifier};
+use image_anonymizer::ocr::mask_list::{MaskPattern, load_mask_patterns, load_mask_terms};
+use image_anonymizer::ocr::masking::SensitiveTextCriteria;
+use image_anonymizer::ocr::secret_hashes::load_secret_hashes;
+use image_anonymizer::ocr::secrets::load_gitleaks_rules;
+use image_anonymizer::vision::MAX_BATCH_SIZE;
+use image_anonymizer::{
+    Anonymizer, ApiClient, ClientConfig, FailurePolicy, RetryPolicy, parse_mask_texts,
+};
+use std::env;
 use std::fs;
 use std::path::PathBuf;
-use tracing::{debug, error, info};
+use std::time::Duration;
+use tracing::{debug, error, info, warn};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "A tool to mask sensitive content in images")]
 struct Args {
-    #[arg(required = true)]
+    #[arg(required = true,
+          help = "Image to process, or a directory whose images are all processed")]
     input_file: PathBuf,
 
     #[arg(short, long, default_value = "./output")]
@@ -20,22 +35,169 @@ struct Args {
     #[arg(short, long)]
     mask_texts: Option<String>,
 
-    #[arg(short, long)]
+    #[arg(long, value_name = "PATH",
+          help = "File of texts to mask, one per line, matched ignoring case, can be repeated")]
+    mask_file: Vec<PathBuf>,
+
+    #[arg(long, help = "Only mask the texts to mask as whole words, not inside other words")]
+    mask_whole_words: bool,
+
+    #[arg(long, value_name = "PATH",
+          help = "File of salted SHA-256 digests of secrets to mask, can be repeated")]
+    mask_hashes: Vec<PathBuf>,
+
+    #[arg(long, value_name = "REGEX",
+          help = "Regular expression whose matches are masked, can be repeated")]
+    mask_regex: Vec<String>,
+
+    #[arg(long, value_name = "PATH",
+          help = "File of regular expressions to mask, one per line, optionally as NAME = REGEX")]
+    mask_patterns: Option<PathBuf>,
+
+    #[arg(short, long,
+          help = "GCP API key, overrides the GCP_API_KEY environment variable")]
     api_key: Option<String>,
-    
-    #[arg(short = 'f', long, default_value = "false", 
+
+    #[arg(short = 'f', long, default_value = "false",
           help = "Enable face detection and masking")]
     mask_faces: bool,
+
+    #[arg(long, value_enum, default_value_t = FaceBackend::Vision,
+          help = "Face detection backend")]
+    face_detector: FaceBackend,
+
+    #[arg(long, value_enum, default_value_t = ClassifierBackend::Chain,
+          help = "Backend deciding which detected text is sensitive")]
+    classifier: ClassifierBackend,
+
+    #[arg(long, value_enum, value_name = "KINDS", value_delimiter = ',',
+          help = "Kinds of sensitive text detected locally, before the classifier, comma separated [default: all]")]
+    detect: Vec<SensitiveKind>,
+
+    #[arg(long, value_name = "PATH",
+          help = "gitleaks TOML configuration whose rules detect more API keys and tokens, can be repeated")]
+    secret_rules: Vec<PathBuf>,
+
+    #[arg(long, value_name = "BITS", default_value_t = 3.5,
+          help = "Minimum Shannon entropy per character of a token masked as an unknown secret")]
+    entropy_threshold: f64,
+
+    #[arg(long, value_name = "CHARS", default_value_t = 20,
+          help = "Minimum length of a token masked for its entropy")]
+    entropy_min_length: usize,
+
+    #[arg(long, value_name = "COUNT", default_value_t = 3,
+          help = "Minimum number of character classes (lowercase, uppercase, digits, symbols) of a token masked for its entropy")]
+    entropy_min_classes: usize,
+
+    #[arg(long, help = "Do not mask tokens for their entropy")]
+    no_entropy: bool,
+
+    #[arg(long, value_name = "LABELS", value_delimiter = ',',
+          help = "Form labels whose value is masked, comma separated, instead of the built-in ones (empty to disable)")]
+    value_labels: Option<Vec<String>>,
+
+    #[arg(long, value_name = "HEADERS", value_delimiter = ',',
+          help = "Table headers whose column cells are masked, comma separated (none by default)")]
+    column_headers: Option<Vec<String>>,
+
+    #[arg(long, value_name = "PATH",
+          help = "Write a JSON audit log of the masked regions")]
+    report: Option<PathBuf>,
+
+    #[arg(long, requires = "report",
+          help = "Include the raw masked text in the audit log")]
+    report_include_text: bool,
+
+    #[arg(long, value_name = "URL", default_value = DEFAULT_VISION_BASE_URL,
+          help = "Base URL of the Google Cloud Vision API")]
+    vision_url: String,
+
+    #[arg(long, value_name = "URL", default_value = DEFAULT_GEMINI_BASE_URL,
+          help = "Base URL of the Google Gemini API")]
+    gemini_url: String,
+
+    #[arg(long, value_name = "SECONDS", default_value_t = 10,
+          help = "Connection timeout for API requests, 0 to disable")]
+    connect_timeout: u64,
+
+    #[arg(long, value_name = "SECONDS", default_value_t = 60,
+          help = "Total timeout for each API request, 0 to disable")]
+    timeout: u64,
+
+    #[arg(long, value_name = "URL",
+          help = "Proxy used for API requests")]
+    proxy: Option<String>,
+
+    #[arg(long, value_name = "PATH",
+          help = "Additional PEM root certificate to trust, can be repeated")]
+    ca_cert: Vec<PathBuf>,
+
+    #[arg(long, value_name = "COUNT", default_value_t = 3,
+          help = "Retries of API requests failing with rate limits or server errors")]
+    max_retries: u32,
+
+    #[arg(long, value_name = "MILLISECONDS", default_value_t = 500,
+          help = "Delay before the first retry, doubled after each retry")]
+    retry_backoff_ms: u64,
+
+    #[arg(long, value_name = "COUNT", default_value_t = MAX_BATCH_SIZE,
+          help = "Images per Vision API request when processing a directory (at most 16)")]
+    batch_size: usize,
+
+    #[arg(long, value_enum, value_name = "MODE", num_args = 0..=1, require_equals = true,
+          default_missing_value = "abort",
+          help = "Fail closed when a backend fails: abort (default) or blackout the whole image")]
+    strict: Option<StrictMode>,
+
+    #[arg(long, value_enum, value_name = "UNIT", num_args = 0..=1, require_equals = true,
+          default_missing_value = "word",
+          help = "Use Vision document text detection, masking by line, word (default) or character")]
+    document_text: Option<DocumentUnit>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct MaskText {
-    text: String,
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum FaceBackend {
+    /// Google Cloud Vision API
+    Vision,
+    /// Offline SeetaFace cascade detector, no network access
+    #[cfg(feature = "local-faces")]
+    Local,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SensitiveTextsResponse {
-    sensitive_texts: Vec<String>,
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum StrictMode {
+    /// Stop processing the image, no output is written
+    Abort,
+    /// Write a fully blacked out image
+    Blackout,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum DocumentUnit {
+    Line,
+    Word,
+    Character,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+enum SensitiveKind {
+    ApiKeys,
+    Emails,
+    PhoneNumbers,
+    CreditCards,
+    PersonalNames,
+    CompanyNames,
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ClassifierBackend {
+    /// Google Gemini API only
+    Gemini,
+    /// Offline regular expression rules only
+    Rules,
+    /// Gemini, falling back to the rules when the API fails
+    Chain,
 }
 
 /// Main function
@@ -68,10 +230,14 @@ fn main() -> Result<()> {
 
     let args = Args::parse();
 
-    if std::env::var("GCP_API_KEY").is_err() {
+    let Some(api_key) = args
+        .api_key
+        .clone()
+        .or_else(|| env::var("GCP_API_KEY").ok())
+    else {
         error!("GCP_API_KEY environment variable is not set");
         anyhow::bail!("GCP_API_KEY environment variable is not set");
-    }
+    };
 
     if !args.input_file.exists() {
         error!("Input file does not exist: {:?}", args.input_file);
@@ -83,17 +249,165 @@ fn main() -> Result<()> {
         fs::create_dir_all(&args.output_dir).context("Failed to create output directory")?;
     }
 
+    let seconds = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
+    let client = ApiClient::new(ClientConfig {
+        vision_base_url: args.vision_url.clone(),
+        gemini_base_url: args.gemini_url.clone(),
+        connect_timeout: seconds(args.connect_timeout),
+        timeout: seconds(args.timeout),
+        proxy: args.proxy.clone(),
+        ca_certs: args.ca_cert.clone(),
+        retry: RetryPolicy {
+            max_attempts: args.max_retries + 1,
+            initial_backoff: Duration::from_millis(args.retry_backoff_ms),
+            ..RetryPolicy::default()
+        },
+    })?;
+
+    let mut builder = Anonymizer::builder()
+        .api_key(api_key.clone())
+        .client(client.clone())
+        .batch_size(args.batch_size)
+        .on_failure(match args.strict {
+            None => FailurePolicy::Lenient,
+            Some(StrictMode::Abort) => FailurePolicy::Abort,
+            Some(StrictMode::Blackout) => FailurePolicy::Blackout,
+        })
+        .mask_texts(parse_mask_texts(args.mask_texts.as_deref()))
+        .mask_whole_words(args.mask_whole_words);
+
+    for path in &args.mask_file {
+        let terms = load_mask_terms(path)?;
+        info!("Masking {} terms from {}", terms.len(), path.display());
+        builder = builder.mask_texts(terms);
+    }
+    for path in &args.mask_hashes {
+        let secrets = load_secret_hashes(path)?;
+        info!("Masking {} hashed secrets from {}", secrets.len(), path.display());
+        builder = builder.secret_hashes(secrets);
+    }
+
+    let mut patterns = args
+        .mask_regex
+        .iter()
+        .map(|pattern| MaskPattern::unnamed(pattern))
+        .collect::<image_anonymizer::Result<Vec<_>>>()?;
+    if let Some(path) = &args.mask_patterns {
+        patterns.extend(load_mask_patterns(path)?);
+    }
+    if !patterns.is_empty() {
+        info!("Masking {} patterns", patterns.len());
+        builder = builder.mask_patterns(patterns);
+    }
+
+    let mut criteria = SensitiveTextCriteria::default();
+    if !args.detect.is_empty() {
+        let enabled = |kind| args.detect.contains(&kind);
+        info!("Detecting {:?} locally", args.detect);
+        criteria.api_keys = enabled(SensitiveKind::ApiKeys);
+        criteria.emails = enabled(SensitiveKind::Emails);
+        criteria.phone_numbers = enabled(SensitiveKind::PhoneNumbers);
+        criteria.credit_cards = enabled(SensitiveKind::CreditCards);
+        criteria.personal_names = enabled(SensitiveKind::PersonalNames);
+        criteria.company_names = enabled(SensitiveKind::CompanyNames);
+    }
+    criteria.entropy = (!args.no_entropy).then_some(EntropyThresholds {
+        min_entropy: args.entropy_threshold,
+        min_length: args.entropy_min_length,
+        min_classes: args.entropy_min_classes,
+    });
+    if let Some(labels) = &args.value_labels {
+        criteria.value_labels = labels
+            .iter()
+            .map(|label| label.trim().to_string())
+            .filter(|label| !label.is_empty())
+            .collect();
+    }
+    if let Some(headers) = &args.column_headers {
+        criteria.column_headers = headers
+            .iter()
+            .map(|header| header.trim().to_string())
+            .filter(|header| !header.is_empty())
+            .collect();
+    }
+    for path in &args.secret_rules {
+        let rules = load_gitleaks_rules(path)?;
+        info!("Loaded {} secret rules from {}", rules.rules().len(), path.display());
+        criteria.secret_rules = criteria.secret_rules.merge(rules);
+    }
+    builder = builder.criteria(criteria);
+
+    if let Some(unit) = args.document_text {
+        info!("Document text detection enabled ({:?} granularity)", unit);
+        builder = builder.document_text(match unit {
+            DocumentUnit::Line => TextGranularity::Line,
+            DocumentUnit::Word => TextGranularity::Word,
+            DocumentUnit::Character => TextGranularity::Character,
+        });
+    }
+
     if args.mask_faces {
-        info!("Face masking enabled");
+        info!("Face masking enabled ({:?} backend)", args.face_detector);
+        builder = match args.face_detector {
+            FaceBackend::Vision => builder.mask_faces(true),
+            #[cfg(feature = "local-faces")]
+            FaceBackend::Local => builder.face_detector(LocalFaceDetector::default()),
+        };
     }
 
-    process_image(
-        &args.input_file,
-        &args.output_dir,
-        args.mask_texts.as_deref(),
-        args.mask_faces,
-    )
-    .context("Failed to process image")?;
+    builder = match args.classifier {
+        ClassifierBackend::Gemini => {
+            let model =
+                env::var("GEMINI_MODEL").unwrap_or_else(|_| DEFAULT_GEMINI_MODEL.to_string());
+            builder.classifier(GeminiClassifier::new(api_key, model).with_client(client))
+        }
+        ClassifierBackend::Rules => builder.classifier(RuleBasedClassifier::default()),
+        // The builder defaults to Gemini with the rules as fallback, or to
+        // Gemini alone in strict mode
+        ClassifierBackend::Chain => builder,
+    };
+
+    let anonymizer = builder.build()?;
+
+    let reports = if args.input_file.is_dir() {
+        anonymizer
+            .process_dir(&args.input_file, &args.output_dir)
+            .context("Failed to process directory")?
+    } else {
+        let result = anonymizer.process_file(&args.input_file, &args.output_dir);
+        vec![(args.input_file.clone(), result)]
+    };
+
+    let mut audit_log = AuditLog::default();
+    let mut failures = 0;
+    for (input, result) in &reports {
+        match result {
+            Ok(report) => {
+                if report.blacked_out {
+                    warn!("Blacked out {:?} after a backend failure", input);
+                } else if !report.is_complete() {
+                    warn!("Results for {:?} are partial: {:?}", input, report.failures);
+                }
+                audit_log
+                    .records
+                    .push(AuditRecord::from_report(report, args.report_include_text));
+            }
+            Err(e) => {
+                error!("Failed to process {:?}: {}", input, e);
+                audit_log.records.push(AuditRecord::from_failure(input, e));
+                failures += 1;
+            }
+        }
+    }
+
+    if let Some(report_path) = &args.report {
+        audit_log.write(report_path)?;
+        info!("Wrote audit log to: {:?}", report_path);
+    }
+
+    if failures > 0 {
+        anyhow::bail!("Failed to process {} of {} images", failures, reports.len());
+    }
 
     info!("Image processing completed successfully");
     Ok(())
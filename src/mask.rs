@@ -0,0 +1,151 @@
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+use serde::Serialize;
+
+/// Rectangular image region, inclusive on all sides
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct MaskRegion {
+    pub min_x: u32,
+    pub min_y: u32,
+    pub max_x: u32,
+    pub max_y: u32,
+}
+
+impl MaskRegion {
+    /// Width of the region in pixels
+    pub fn width(&self) -> u32 {
+        self.max_x - self.min_x + 1
+    }
+
+    /// Height of the region in pixels
+    pub fn height(&self) -> u32 {
+        self.max_y - self.min_y + 1
+    }
+
+    /// Build the bounding rectangle of polygon vertices, clamped to the image
+    ///
+    /// # Arguments
+    ///
+    /// * `points` - The `(x, y)` vertices of the polygon
+    /// * `width` - The width of the image
+    /// * `height` - The height of the image
+    ///
+    /// # Returns
+    ///
+    /// * `Option<MaskRegion>` - The region, or `None` if there are no vertices
+    ///   or the polygon lies outside the image
+    ///
+    pub fn from_points(
+        points: impl IntoIterator<Item = (i32, i32)>,
+        width: u32,
+        height: u32,
+    ) -> Option<Self> {
+        let mut bounds: Option<(i32, i32, i32, i32)> = None;
+        for (x, y) in points {
+            bounds = Some(match bounds {
+                Some((min_x, min_y, max_x, max_y)) => {
+                    (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y))
+                }
+                None => (x, y, x, y),
+            });
+        }
+
+        let (min_x, min_y, max_x, max_y) = bounds?;
+        if width == 0 || height == 0 || max_x < 0 || max_y < 0 {
+            return None;
+        }
+
+        let min_x = min_x.max(0) as u32;
+        let min_y = min_y.max(0) as u32;
+        if min_x >= width || min_y >= height {
+            return None;
+        }
+
+        Some(Self {
+            min_x,
+            min_y,
+            max_x: (max_x as u32).min(width - 1),
+            max_y: (max_y as u32).min(height - 1),
+        })
+    }
+
+    /// Smallest region enclosing both regions
+    pub fn union(&self, other: &MaskRegion) -> MaskRegion {
+        MaskRegion {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+}
+
+/// How a sensitive region is hidden
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MaskStyle {
+    /// Replace every pixel with a fixed RGBA color
+    Solid([u8; 4]),
+    /// Pixelate the region with square blocks of the given size
+    Mosaic(u32),
+}
+
+impl MaskStyle {
+    /// Default style for text: semi-transparent black box
+    pub const TEXT_DEFAULT: MaskStyle = MaskStyle::Solid([0, 0, 0, 128]);
+    /// Default style for faces: 16 pixel mosaic
+    pub const FACE_DEFAULT: MaskStyle = MaskStyle::Mosaic(16);
+
+    /// Apply the style to a region of an image
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - The image to mask
+    /// * `region` - The region to hide
+    ///
+    pub fn apply(&self, image: &mut DynamicImage, region: &MaskRegion) {
+        let (width, height) = image.dimensions();
+        let max_x = region.max_x.min(width.saturating_sub(1));
+        let max_y = region.max_y.min(height.saturating_sub(1));
+
+        match *self {
+            MaskStyle::Solid(color) => {
+                let color = Rgba(color);
+                for y in region.min_y..=max_y {
+                    for x in region.min_x..=max_x {
+                        image.put_pixel(x, y, color);
+                    }
+                }
+            }
+            MaskStyle::Mosaic(block_size) => {
+                let block_size = block_size.max(1);
+                for block_y in (region.min_y..=max_y).step_by(block_size as usize) {
+                    for block_x in (region.min_x..=max_x).step_by(block_size as usize) {
+                        let end_x = (block_x + block_size - 1).min(max_x);
+                        let end_y = (block_y + block_size - 1).min(max_y);
+
+                        // Calculate average color for the block
+                        let mut sums = [0u32; 4];
+                        let mut pixel_count = 0u32;
+                        for y in block_y..=end_y {
+                            for x in block_x..=end_x {
+                                let pixel = image.get_pixel(x, y);
+                                for (sum, channel) in sums.iter_mut().zip(pixel.0) {
+                                    *sum += channel as u32;
+                                }
+                                pixel_count += 1;
+                            }
+                        }
+
+                        let avg_pixel = Rgba(sums.map(|sum| (sum / pixel_count.max(1)) as u8));
+
+                        // Fill the block with the average color
+                        for y in block_y..=end_y {
+                            for x in block_x..=end_x {
+                                image.put_pixel(x, y, avg_pixel);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,301 @@
+use regex::Regex;
+use serde::Serialize;
+use std::ops::Range;
+use tracing::{debug, error};
+
+use super::gemini::GeminiClassifier;
+use crate::error::{Error, Result};
+
+/// Outcome of a sensitivity classification
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Verdict {
+    /// Whether the text contains sensitive information
+    pub sensitive: bool,
+    /// Short human readable explanation of the decision
+    pub reason: String,
+    /// Name of the classifier that made the decision
+    pub classifier: String,
+}
+
+impl Verdict {
+    /// Verdict marking text as sensitive
+    pub fn sensitive(reason: impl Into<String>, classifier: impl Into<String>) -> Self {
+        Self {
+            sensitive: true,
+            reason: reason.into(),
+            classifier: classifier.into(),
+        }
+    }
+
+    /// Verdict marking text as not sensitive
+    pub fn not_sensitive(reason: impl Into<String>, classifier: impl Into<String>) -> Self {
+        Self {
+            sensitive: false,
+            reason: reason.into(),
+            classifier: classifier.into(),
+        }
+    }
+}
+
+/// A backend that decides whether a piece of text is sensitive
+pub trait SensitivityClassifier: Send + Sync {
+    /// Short identifier of the classifier, used in logs and reports
+    fn name(&self) -> &str;
+
+    /// Classify a piece of text
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text to classify
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Verdict>` - The classification verdict
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If the classifier could not reach a verdict
+    ///
+    fn classify(&self, text: &str) -> Result<Verdict>;
+
+    /// Locate the sensitive parts of a text this classifier marked as sensitive
+    ///
+    /// Only the located parts are masked. The default implementation
+    /// locates nothing, so the whole text is masked.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The classified text
+    /// * `verdict` - The verdict returned for the text
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Range<usize>>` - Byte ranges of the sensitive parts, empty if unknown
+    ///
+    fn sensitive_spans(&self, text: &str, verdict: &Verdict) -> Vec<Range<usize>> {
+        let _ = (text, verdict);
+        Vec::new()
+    }
+
+    /// Find sensitive parts of a longer text, such as a line of several words
+    ///
+    /// Only classifiers that can search text cheaply and offline implement
+    /// this. The default implementation finds nothing.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text to search
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<(Range<usize>, Verdict)>>` - Byte range and verdict of each sensitive part
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If the classifier could not search the text
+    ///
+    fn find_sensitive(&self, text: &str) -> Result<Vec<(Range<usize>, Verdict)>> {
+        let _ = text;
+        Ok(Vec::new())
+    }
+}
+
+/// A named regular expression used by [`RuleBasedClassifier`]
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub pattern: Regex,
+}
+
+/// Offline classifier that marks text as sensitive when any rule matches
+#[derive(Debug, Clone)]
+pub struct RuleBasedClassifier {
+    rules: Vec<Rule>,
+}
+
+/// Default rules for the rule-based classifier
+///
+/// # Returns
+///
+/// * `RuleBasedClassifier` - A classifier with rules for emails, phone numbers and long digit sequences
+///
+impl Default for RuleBasedClassifier {
+    fn default() -> Self {
+        let builtin = [
+            ("email", r"[^\s@]+@[^\s@]+\.[^\s@]+"),
+            ("phone_number", r"\+?\d[\d\s().-]{7,}\d"),
+            ("digit_sequence", r"(?:\d[ -]?){9,}"),
+        ];
+
+        Self {
+            rules: builtin
+                .iter()
+                .map(|(name, pattern)| Rule {
+                    name: name.to_string(),
+                    pattern: Regex::new(pattern).expect("built-in rule must compile"),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl RuleBasedClassifier {
+    /// Create a classifier without any rules
+    pub fn empty() -> Self {
+        Self { rules: Vec::new() }
+    }
+
+    /// Add a named regular expression rule
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If the pattern is not a valid regular expression
+    ///
+    pub fn with_rule(mut self, name: &str, pattern: &str) -> Result<Self> {
+        let pattern = Regex::new(pattern)
+            .map_err(|e| Error::Config(format!("Invalid pattern for rule '{}': {}", name, e)))?;
+        self.rules.push(Rule {
+            name: name.to_string(),
+            pattern,
+        });
+        Ok(self)
+    }
+
+    /// Byte range and verdict of every match of every rule
+    fn find_rules(&self, text: &str) -> Vec<(Range<usize>, Verdict)> {
+        self.rules
+            .iter()
+            .flat_map(|rule| {
+                rule.pattern
+                    .find_iter(text)
+                    .filter(|found| !found.is_empty())
+                    .map(|found| {
+                        let reason = format!("matched rule '{}'", rule.name);
+                        (found.range(), Verdict::sensitive(reason, self.name()))
+                    })
+            })
+            .collect()
+    }
+}
+
+impl SensitivityClassifier for RuleBasedClassifier {
+    fn name(&self) -> &str {
+        "rules"
+    }
+
+    fn classify(&self, text: &str) -> Result<Verdict> {
+        let verdict = match self.rules.iter().find(|rule| rule.pattern.is_match(text)) {
+            Some(rule) => Verdict::sensitive(format!("matched rule '{}'", rule.name), self.name()),
+            None => Verdict::not_sensitive("no rule matched", self.name()),
+        };
+        Ok(verdict)
+    }
+
+    fn sensitive_spans(&self, text: &str, verdict: &Verdict) -> Vec<Range<usize>> {
+        if !verdict.sensitive || verdict.classifier != self.name() {
+            return Vec::new();
+        }
+        self.find_rules(text)
+            .into_iter()
+            .map(|(range, _)| range)
+            .collect()
+    }
+
+    fn find_sensitive(&self, text: &str) -> Result<Vec<(Range<usize>, Verdict)>> {
+        Ok(self.find_rules(text))
+    }
+}
+
+/// Combinator that asks each classifier in turn until one returns a verdict
+///
+/// A classifier that fails (for example because its API is unreachable) is
+/// skipped and the next one is consulted. The combinator only fails when
+/// every classifier failed. Longer texts are searched the same way: a
+/// fallback never searches a text the classifiers before it could search.
+pub struct ChainClassifier {
+    classifiers: Vec<Box<dyn SensitivityClassifier>>,
+}
+
+impl ChainClassifier {
+    /// Create a chain that consults the classifiers in the given order
+    pub fn new(classifiers: Vec<Box<dyn SensitivityClassifier>>) -> Self {
+        Self { classifiers }
+    }
+}
+
+impl SensitivityClassifier for ChainClassifier {
+    fn name(&self) -> &str {
+        "chain"
+    }
+
+    fn classify(&self, text: &str) -> Result<Verdict> {
+        let mut last_error = None;
+
+        for classifier in &self.classifiers {
+            match classifier.classify(text) {
+                Ok(verdict) => return Ok(verdict),
+                Err(err) => {
+                    error!(
+                        "Classifier '{}' failed, trying next: {}",
+                        classifier.name(),
+                        err
+                    );
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| Error::Config("No classifiers configured".to_string())))
+    }
+
+    fn sensitive_spans(&self, text: &str, verdict: &Verdict) -> Vec<Range<usize>> {
+        // The verdict comes from one of the chained classifiers
+        self.classifiers
+            .iter()
+            .map(|classifier| classifier.sensitive_spans(text, verdict))
+            .find(|spans| !spans.is_empty())
+            .unwrap_or_default()
+    }
+
+    fn find_sensitive(&self, text: &str) -> Result<Vec<(Range<usize>, Verdict)>> {
+        let mut last_error = None;
+
+        for classifier in &self.classifiers {
+            match classifier.find_sensitive(text) {
+                Ok(found) => return Ok(found),
+                Err(err) => {
+                    error!(
+                        "Classifier '{}' failed to search text, trying next: {}",
+                        classifier.name(),
+                        err
+                    );
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| Error::Config("No classifiers configured".to_string())))
+    }
+}
+
+/// Classifier used when none is configured explicitly
+///
+/// Gemini is consulted first when `GCP_API_KEY` is set, with the rule-based
+/// classifier as a fallback for API failures.
+///
+/// # Returns
+///
+/// * `Box<dyn SensitivityClassifier>` - The default classifier
+///
+pub fn default_classifier() -> Box<dyn SensitivityClassifier> {
+    match GeminiClassifier::from_env() {
+        Ok(gemini) => Box::new(ChainClassifier::new(vec![
+            Box::new(gemini),
+            Box::new(RuleBasedClassifier::default()),
+        ])),
+        Err(err) => {
+            debug!("Gemini unavailable, using rule-based classifier: {}", err);
+            Box::new(RuleBasedClassifier::default())
+        }
+    }
+}
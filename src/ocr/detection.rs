@@ -1,10 +1,13 @@
-use anyhow::{Context, Result};
-use base64::{Engine as _, engine::general_purpose};
-use reqwest::blocking::Client;
-use serde::{Deserialize, Serialize};
+use image::DynamicImage;
+use serde::Deserialize;
 use std::env;
 use std::path::Path;
-use tracing::{debug, error};
+use tracing::debug;
+
+use crate::client::ApiClient;
+use crate::error::{Error, Result};
+use crate::ocr::document::TextGranularity;
+use crate::vision::{VisionClient, VisionFeature};
 
 #[derive(Debug, Deserialize)]
 pub struct TextDetectionResponse {
@@ -39,27 +42,110 @@ pub struct Vertex {
     pub y: i32,
 }
 
-#[derive(Debug, Serialize)]
-struct TextDetectionRequest {
-    requests: Vec<Request>,
+/// A backend that finds text in an image
+///
+/// Implementations follow the Google Cloud Vision convention: when any text is
+/// found, the first annotation covers the whole text of the image and the
+/// following annotations are the individual words.
+pub trait TextDetector: Send + Sync {
+    /// Short identifier of the detector, used in logs and reports
+    fn name(&self) -> &str {
+        "custom"
+    }
+
+    /// Detect text in an in-memory image
+    ///
+    /// # Arguments
+    ///
+    /// * `image` - The image to analyze
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<TextAnnotation>>` - The detected text annotations
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If the detection fails
+    ///
+    fn detect_text(&self, image: &DynamicImage) -> Result<Vec<TextAnnotation>>;
 }
 
-#[derive(Debug, Serialize)]
-struct Request {
-    image: Image,
-    features: Vec<Feature>,
+/// Text detector backed by the Google Cloud Vision API
+#[derive(Debug, Clone)]
+pub struct VisionTextDetector {
+    vision: VisionClient,
+    document: Option<TextGranularity>,
 }
 
-#[derive(Debug, Serialize)]
-struct Image {
-    content: String,
+impl VisionTextDetector {
+    /// Create a detector that authenticates with the given API key
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            vision: VisionClient::new(api_key),
+            document: None,
+        }
+    }
+
+    /// Use `DOCUMENT_TEXT_DETECTION` and report the text at the given granularity
+    ///
+    /// Document detection is better suited to dense text such as scanned
+    /// pages and forms, and gives the geometry of every line, word and
+    /// character.
+    pub fn document(mut self, granularity: TextGranularity) -> Self {
+        self.document = Some(granularity);
+        self
+    }
+
+    /// Send requests through the given client instead of the shared default one
+    pub fn with_client(mut self, client: ApiClient) -> Self {
+        self.vision = self.vision.with_client(client);
+        self
+    }
+
+    /// Create a detector using the `GCP_API_KEY` environment variable
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If `GCP_API_KEY` is not set
+    ///
+    pub fn from_env() -> Result<Self> {
+        let api_key = env::var("GCP_API_KEY").map_err(|_| Error::missing_api_key())?;
+        Ok(Self::new(api_key))
+    }
+
+    /// Detect text in an encoded image (PNG, JPEG, ...)
+    ///
+    /// # Arguments
+    ///
+    /// * `image_data` - The encoded image bytes
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<TextAnnotation>>` - The detected text annotations
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If the request fails or the response cannot be parsed
+    ///
+    pub fn detect_bytes(&self, image_data: &[u8]) -> Result<Vec<TextAnnotation>> {
+        let feature = match self.document {
+            Some(_) => VisionFeature::DocumentTextDetection,
+            None => VisionFeature::TextDetection,
+        };
+        let response = self.vision.annotate(image_data, &[feature])?;
+        Ok(response.texts(self.document))
+    }
 }
 
-#[derive(Debug, Serialize)]
-struct Feature {
-    #[serde(rename = "type")]
-    feature_type: String,
-    max_results: i32,
+impl TextDetector for VisionTextDetector {
+    fn name(&self) -> &str {
+        "vision"
+    }
+
+    fn detect_text(&self, image: &DynamicImage) -> Result<Vec<TextAnnotation>> {
+        let image_data = crate::encode_png(image)?;
+        self.detect_bytes(&image_data)
+    }
 }
 
 /// Detect text in an image using the Google Cloud Vision API
@@ -74,59 +160,13 @@ struct Feature {
 ///
 /// # Errors
 ///
-/// * `anyhow::Error` - If the image processing fails
+/// * `Error` - If the image processing fails
 ///
 pub fn detect_text_with_api(image_path: &Path) -> Result<Vec<TextAnnotation>> {
-    let api_key = env::var("GCP_API_KEY").context("GCP_API_KEY environment variable not set")?;
+    let detector = VisionTextDetector::from_env()?;
     debug!("image_path: {}", image_path.display());
 
-    let image_data = std::fs::read(image_path).context("Failed to read image file")?;
-    let base64_image = general_purpose::STANDARD.encode(&image_data);
-
-    let request = TextDetectionRequest {
-        requests: vec![Request {
-            image: Image {
-                content: base64_image,
-            },
-            features: vec![Feature {
-                feature_type: "TEXT_DETECTION".to_string(),
-                max_results: 100,
-            }],
-        }],
-    };
-
-    let client = Client::new();
-    let response = client
-        .post(&format!(
-            "https://vision.googleapis.com/v1/images:annotate?key={}",
-            api_key
-        ))
-        .json(&request)
-        .send()
-        .context("Failed to send request to Google Cloud Vision API")?;
-
-    let response_text = response.text().context("Failed to get response text")?;
-
-    if response_text.len() > 1000 {
-        debug!(
-            "Response text (first 1000 chars): {}",
-            &response_text[..1000]
-        );
-        debug!("Response text length: {}", response_text.len());
-    } else {
-        debug!("Response text: {}", &response_text);
-    }
-
-    let response_body: TextDetectionResponse = serde_json::from_str(&response_text)
-        .context("Failed to parse Google Cloud Vision API response")?;
-
-    if response_body.responses.is_empty() {
-        error!("No responses from Google Cloud Vision API");
-        anyhow::bail!("No responses from Google Cloud Vision API");
-    }
-
-    let annotations = response_body.responses[0].text_annotations.clone();
-    debug!("Detected {} text annotations", annotations.len());
-
-    Ok(annotations)
+    let image_data =
+        std::fs::read(image_path).map_err(|e| Error::io("Failed to read image file", e))?;
+    detector.detect_bytes(&image_data)
 }
@@ -0,0 +1,367 @@
+use serde::Deserialize;
+use std::ops::Range;
+
+use super::detection::{BoundingPoly, TextAnnotation, Vertex};
+
+/// Structured text returned by the Vision `DOCUMENT_TEXT_DETECTION` feature
+///
+/// The text is organized as pages, blocks, paragraphs, words and symbols
+/// (characters), each with its own bounding box and confidence.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct FullTextAnnotation {
+    #[serde(default)]
+    pub pages: Vec<Page>,
+    /// The whole text of the image
+    #[serde(default)]
+    pub text: String,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Page {
+    #[serde(default)]
+    pub width: u32,
+    #[serde(default)]
+    pub height: u32,
+    #[serde(default)]
+    pub blocks: Vec<Block>,
+    /// Recognition confidence (0.0 to 1.0), when reported
+    #[serde(default)]
+    pub confidence: Option<f32>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Block {
+    #[serde(default)]
+    #[serde(rename = "boundingBox")]
+    pub bounding_box: Option<BoundingPoly>,
+    #[serde(default)]
+    pub paragraphs: Vec<Paragraph>,
+    /// Kind of block, e.g. `TEXT`, `TABLE` or `PICTURE`
+    #[serde(default)]
+    #[serde(rename = "blockType")]
+    pub block_type: Option<String>,
+    /// Recognition confidence (0.0 to 1.0), when reported
+    #[serde(default)]
+    pub confidence: Option<f32>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Paragraph {
+    #[serde(default)]
+    #[serde(rename = "boundingBox")]
+    pub bounding_box: Option<BoundingPoly>,
+    #[serde(default)]
+    pub words: Vec<Word>,
+    /// Recognition confidence (0.0 to 1.0), when reported
+    #[serde(default)]
+    pub confidence: Option<f32>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Word {
+    #[serde(default)]
+    #[serde(rename = "boundingBox")]
+    pub bounding_box: Option<BoundingPoly>,
+    #[serde(default)]
+    pub symbols: Vec<Symbol>,
+    /// Recognition confidence (0.0 to 1.0), when reported
+    #[serde(default)]
+    pub confidence: Option<f32>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct Symbol {
+    #[serde(default)]
+    #[serde(rename = "boundingBox")]
+    pub bounding_box: Option<BoundingPoly>,
+    /// The character, possibly several bytes long
+    #[serde(default)]
+    pub text: String,
+    #[serde(default)]
+    pub property: Option<TextProperty>,
+    /// Recognition confidence (0.0 to 1.0), when reported
+    #[serde(default)]
+    pub confidence: Option<f32>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct TextProperty {
+    #[serde(default)]
+    #[serde(rename = "detectedBreak")]
+    pub detected_break: Option<DetectedBreak>,
+}
+
+/// Break detected after a symbol
+#[derive(Debug, Deserialize, Clone)]
+pub struct DetectedBreak {
+    #[serde(rename = "type")]
+    pub break_type: BreakType,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum BreakType {
+    Unknown,
+    Space,
+    SureSpace,
+    /// Line-wrapping break
+    EolSureSpace,
+    /// End-line hyphen that is not present in the text
+    Hyphen,
+    /// Line break that ends a paragraph
+    LineBreak,
+    #[serde(other)]
+    Other,
+}
+
+/// Unit of the text annotations built from a [`FullTextAnnotation`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TextGranularity {
+    /// One annotation per line of text
+    Line,
+    /// One annotation per word
+    #[default]
+    Word,
+    /// One annotation per word, masked character by character
+    ///
+    /// Single characters cannot be classified, so text is classified by
+    /// word and line as with [`Self::Word`], and the sensitive characters are
+    /// then covered by their own boxes.
+    Character,
+}
+
+/// Bounding box of one character of a text annotation
+#[derive(Debug, Clone)]
+pub struct SymbolBox {
+    /// Byte range of the character in the annotation description
+    pub range: Range<usize>,
+    pub bounding_poly: BoundingPoly,
+}
+
+impl Word {
+    /// The text of the word, without the trailing break
+    pub fn text(&self) -> String {
+        self.symbols
+            .iter()
+            .map(|symbol| symbol.text.as_str())
+            .collect()
+    }
+
+    /// Break detected after the last symbol of the word
+    pub fn detected_break(&self) -> Option<BreakType> {
+        let property = self.symbols.last()?.property.as_ref()?;
+        Some(property.detected_break.as_ref()?.break_type)
+    }
+
+    /// Bounding boxes of the symbols, relative to the start of the word text
+    ///
+    /// # Arguments
+    ///
+    /// * `offset` - Byte offset of the word in the annotation text
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<SymbolBox>` - The boxes of the symbols that have one
+    ///
+    pub fn symbol_boxes(&self, offset: usize) -> Vec<SymbolBox> {
+        let mut start = offset;
+        let mut boxes = Vec::with_capacity(self.symbols.len());
+        for symbol in &self.symbols {
+            let end = start + symbol.text.len();
+            if let Some(bounding_poly) = &symbol.bounding_box {
+                boxes.push(SymbolBox {
+                    range: start..end,
+                    bounding_poly: bounding_poly.clone(),
+                });
+            }
+            start = end;
+        }
+        boxes
+    }
+}
+
+impl FullTextAnnotation {
+    /// Every word of the document, in reading order
+    pub fn words(&self) -> impl Iterator<Item = &Word> {
+        self.pages
+            .iter()
+            .flat_map(|page| &page.blocks)
+            .flat_map(|block| &block.paragraphs)
+            .flat_map(|paragraph| &paragraph.words)
+    }
+
+    /// Remove the words recognized with a confidence below a minimum
+    ///
+    /// Words without a reported confidence are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `min_confidence` - The minimum confidence (0.0 to 1.0) of the words to keep
+    ///
+    pub fn retain_confident(&mut self, min_confidence: f32) {
+        for paragraph in self
+            .pages
+            .iter_mut()
+            .flat_map(|page| &mut page.blocks)
+            .flat_map(|block| &mut block.paragraphs)
+        {
+            let mut kept: Vec<Word> = Vec::with_capacity(paragraph.words.len());
+            for word in paragraph.words.drain(..) {
+                if word
+                    .confidence
+                    .is_none_or(|confidence| confidence >= min_confidence)
+                {
+                    kept.push(word);
+                    continue;
+                }
+                // Keep the line break of a dropped word, so that lines stay apart
+                let ends_line = matches!(
+                    word.detected_break(),
+                    Some(BreakType::EolSureSpace | BreakType::LineBreak | BreakType::Hyphen)
+                );
+                if ends_line
+                    && let Some(previous) = kept.last_mut().and_then(|w| w.symbols.last_mut())
+                {
+                    previous.property = word.symbols.last().and_then(|s| s.property.clone());
+                }
+            }
+            paragraph.words = kept;
+        }
+    }
+
+    /// Convert the hierarchy to text annotations at the given granularity
+    ///
+    /// The annotations follow the `TEXT_DETECTION` convention: the first one
+    /// covers the whole text of the image, the following ones are the lines
+    /// or words.
+    ///
+    /// # Arguments
+    ///
+    /// * `granularity` - The unit of the annotations
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<TextAnnotation>` - The text annotations, empty if no text was found
+    ///
+    pub fn to_annotations(&self, granularity: TextGranularity) -> Vec<TextAnnotation> {
+        self.to_annotations_with_symbols(granularity)
+            .into_iter()
+            .map(|(annotation, _)| annotation)
+            .collect()
+    }
+
+    /// Convert the hierarchy to text annotations, with the box of each character
+    ///
+    /// # Arguments
+    ///
+    /// * `granularity` - The unit of the annotations
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(TextAnnotation, Vec<SymbolBox>)>` - The text annotations as
+    ///   returned by [`Self::to_annotations`], each with its character boxes
+    ///   (none for the leading whole-text annotation)
+    ///
+    pub fn to_annotations_with_symbols(
+        &self,
+        granularity: TextGranularity,
+    ) -> Vec<(TextAnnotation, Vec<SymbolBox>)> {
+        let units: Vec<_> = match granularity {
+            TextGranularity::Line => self.lines(),
+            TextGranularity::Word | TextGranularity::Character => self
+                .words()
+                .map(|word| {
+                    let annotation = TextAnnotation {
+                        description: word.text(),
+                        bounding_poly: word.bounding_box.clone(),
+                    };
+                    (annotation, word.symbol_boxes(0))
+                })
+                .collect(),
+        };
+
+        if units.is_empty() {
+            return Vec::new();
+        }
+
+        let whole_text = TextAnnotation {
+            description: self.text.clone(),
+            bounding_poly: union_box(
+                self.pages
+                    .iter()
+                    .flat_map(|page| &page.blocks)
+                    .filter_map(|block| block.bounding_box.as_ref()),
+            ),
+        };
+        std::iter::once((whole_text, Vec::new()))
+            .chain(units)
+            .collect()
+    }
+
+    /// Group the words into lines, using the breaks detected after them
+    fn lines(&self) -> Vec<(TextAnnotation, Vec<SymbolBox>)> {
+        let mut lines = Vec::new();
+
+        for paragraph in self
+            .pages
+            .iter()
+            .flat_map(|page| &page.blocks)
+            .flat_map(|block| &block.paragraphs)
+        {
+            let mut text = String::new();
+            let mut boxes = Vec::new();
+            let mut symbols = Vec::new();
+            for (index, word) in paragraph.words.iter().enumerate() {
+                symbols.extend(word.symbol_boxes(text.len()));
+                text.push_str(&word.text());
+                boxes.extend(word.bounding_box.as_ref());
+
+                let last = index + 1 == paragraph.words.len();
+                match word.detected_break() {
+                    Some(BreakType::Space | BreakType::SureSpace) if !last => text.push(' '),
+                    Some(BreakType::EolSureSpace | BreakType::LineBreak | BreakType::Hyphen) => {
+                        let annotation = TextAnnotation {
+                            description: std::mem::take(&mut text),
+                            bounding_poly: union_box(boxes.drain(..)),
+                        };
+                        lines.push((annotation, std::mem::take(&mut symbols)));
+                    }
+                    _ => {}
+                }
+            }
+
+            if !text.is_empty() {
+                let annotation = TextAnnotation {
+                    description: text.trim_end().to_string(),
+                    bounding_poly: union_box(boxes.drain(..)),
+                };
+                lines.push((annotation, symbols));
+            }
+        }
+
+        lines
+    }
+}
+
+/// Axis-aligned box enclosing all the given boxes
+fn union_box<'a>(boxes: impl IntoIterator<Item = &'a BoundingPoly>) -> Option<BoundingPoly> {
+    let mut vertices = boxes.into_iter().flat_map(|poly| &poly.vertices).peekable();
+    vertices.peek()?;
+
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (i32::MAX, i32::MAX, i32::MIN, i32::MIN);
+    for vertex in vertices {
+        min_x = min_x.min(vertex.x);
+        min_y = min_y.min(vertex.y);
+        max_x = max_x.max(vertex.x);
+        max_y = max_y.max(vertex.y);
+    }
+
+    Some(BoundingPoly {
+        vertices: vec![
+            Vertex { x: min_x, y: min_y },
+            Vertex { x: max_x, y: min_y },
+            Vertex { x: max_x, y: max_y },
+            Vertex { x: min_x, y: max_y },
+        ],
+    })
+}
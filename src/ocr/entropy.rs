@@ -0,0 +1,113 @@
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use super::classifier::Verdict;
+use super::secrets::shannon_entropy;
+
+/// Name recorded in verdicts for tokens found by their entropy
+pub const ENTROPY_SOURCE: &str = "entropy";
+
+/// When a token is random enough to be a secret
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct EntropyThresholds {
+    /// Minimum Shannon entropy, in bits per character
+    pub min_entropy: f64,
+    /// Minimum number of characters
+    pub min_length: usize,
+    /// Minimum number of character classes among lowercase, uppercase, digits and symbols
+    pub min_classes: usize,
+}
+
+impl Default for EntropyThresholds {
+    fn default() -> Self {
+        Self {
+            min_entropy: 3.5,
+            min_length: 20,
+            min_classes: 3,
+        }
+    }
+}
+
+/// Entropy and character class mix of a token
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenScore {
+    pub entropy: f64,
+    pub classes: usize,
+}
+
+/// Digits the OCR confuses with letters, and the letter they stand for
+const CONFUSABLE_DIGITS: [(char, char); 4] = [('0', 'o'), ('1', 'l'), ('5', 's'), ('8', 'b')];
+
+/// Score a token, reading the digits the OCR confuses with letters as letters
+///
+/// A word read as `C0NFIGURATI0N` or `passw0rd` scores as the word itself, so
+/// that OCR errors do not add a digit class. Random tokens keep their score,
+/// whichever of `O` and `0` the OCR picked.
+pub fn score_token(token: &str) -> TokenScore {
+    // Read the letters in the case of the token, `C0DE` is `CODE`
+    let uppercase = !token.chars().any(|c| c.is_ascii_lowercase());
+    let folded: String = token
+        .chars()
+        .map(
+            |c| match CONFUSABLE_DIGITS.iter().find(|(digit, _)| *digit == c) {
+                Some((_, letter)) if uppercase => letter.to_ascii_uppercase(),
+                Some((_, letter)) => *letter,
+                None => c,
+            },
+        )
+        .collect();
+
+    let has = |class: fn(&char) -> bool| folded.chars().any(|c| class(&c));
+    let classes = [
+        has(char::is_ascii_lowercase),
+        has(char::is_ascii_uppercase),
+        has(char::is_ascii_digit),
+        has(|c| !c.is_ascii_alphanumeric()),
+    ];
+    TokenScore {
+        entropy: shannon_entropy(&folded),
+        classes: classes.into_iter().filter(|&class| class).count(),
+    }
+}
+
+/// Find the tokens random enough to be secrets
+///
+/// Tokens are runs of letters, digits, `+`, `/`, `_` and `-`, with optional
+/// `=` padding, so that labels such as `token=` and surrounding quotes are
+/// not part of them.
+///
+/// # Arguments
+///
+/// * `text` - The text to search
+/// * `thresholds` - When a token counts as a secret
+///
+/// # Returns
+///
+/// * `Vec<(Range<usize>, Verdict)>` - Byte range and verdict of each token
+///
+pub fn find_high_entropy(
+    text: &str,
+    thresholds: &EntropyThresholds,
+) -> Vec<(Range<usize>, Verdict)> {
+    static TOKEN: OnceLock<Regex> = OnceLock::new();
+    let token =
+        TOKEN.get_or_init(|| Regex::new(r"[A-Za-z0-9+/_-]+={0,2}").expect("valid token pattern"));
+
+    token
+        .find_iter(text)
+        .filter(|token| token.as_str().chars().count() >= thresholds.min_length)
+        .filter_map(|token| {
+            let score = score_token(token.as_str());
+            (score.entropy >= thresholds.min_entropy && score.classes >= thresholds.min_classes)
+                .then(|| {
+                    let reason = format!(
+                        "high entropy token ({:.1} bits per character)",
+                        score.entropy
+                    );
+                    (token.range(), Verdict::sensitive(reason, ENTROPY_SOURCE))
+                })
+        })
+        .collect()
+}
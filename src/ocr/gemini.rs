@@ -1,9 +1,14 @@
-use anyhow::{Context, Result};
-use reqwest::blocking::Client;
 use serde::{Deserialize, Serialize};
 use std::env;
 use tracing::{debug, error};
 
+use super::classifier::{SensitivityClassifier, Verdict};
+use crate::client::{API_KEY_HEADER, ApiClient};
+use crate::error::{Error, Result};
+
+/// Name of the Google Gemini API in error messages
+const GEMINI_API: &str = "Google Gemini API";
+
 #[derive(Debug, Serialize)]
 struct GeminiRequest {
     contents: Vec<Content>,
@@ -49,6 +54,157 @@ struct CandidatePart {
     text: String,
 }
 
+/// Default Gemini model used when `GEMINI_MODEL` is not set
+pub const DEFAULT_GEMINI_MODEL: &str = "gemini-2.0-flash-lite";
+
+/// Sensitivity classifier backed by the Google Gemini API
+#[derive(Debug, Clone)]
+pub struct GeminiClassifier {
+    api_key: String,
+    model: String,
+    client: ApiClient,
+}
+
+impl GeminiClassifier {
+    /// Create a classifier using the given API key and model name
+    pub fn new(api_key: impl Into<String>, model: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            model: model.into(),
+            client: ApiClient::shared(),
+        }
+    }
+
+    /// Send requests through the given client instead of the shared default one
+    pub fn with_client(mut self, client: ApiClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Create a classifier using the `GCP_API_KEY` and `GEMINI_MODEL` environment variables
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If `GCP_API_KEY` is not set
+    ///
+    pub fn from_env() -> Result<Self> {
+        let api_key = env::var("GCP_API_KEY").map_err(|_| Error::missing_api_key())?;
+        let model = env::var("GEMINI_MODEL").unwrap_or_else(|_| DEFAULT_GEMINI_MODEL.to_string());
+        Ok(Self::new(api_key, model))
+    }
+
+    /// Analyzes text for sensitive information
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text to analyze
+    ///
+    /// # Returns
+    ///
+    /// * `Result<bool>` - The result of the text analysis
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If the text analysis fails
+    pub fn analyze(&self, text: &str) -> Result<bool> {
+        debug!("Analyzing text sensitivity with Gemini: {}", text);
+
+        let prompt = format!(
+            "Analyze the following text and determine if it contains ACTUAL sensitive information rather than just labels or UI elements. \
+            Respond with only 'true' if it contains real sensitive information, or 'false' if it doesn't.\n\n\
+            Examples of what IS sensitive:\n\
+            - Actual API keys like 'AIzaSyB3X7gtreHx9FGpA_XXXXXXXXXXXXX'\n\
+            - Real email addresses like 'john.doe@example.com'\n\
+            - Actual phone numbers like '+1-555-123-4567'\n\
+            - Real credit card numbers, personal names, addresses, etc.\n\n\
+            Examples of what is NOT sensitive:\n\
+            - Labels like 'API Key', 'Email', 'Credentials', 'Create', 'Password'\n\
+            - Button text like 'Submit', 'Login', 'Dismiss', 'View'\n\
+            - Generic terms like 'Username' or 'Authentication'\n\n\
+            Only mark as 'true' if it appears to be an actual sensitive value, not a UI element or label describing a value.\n\n\
+            Text to analyze: \"{}\"",
+            text
+        );
+
+        let request = GeminiRequest {
+            contents: vec![Content {
+                role: "user".to_string(),
+                parts: vec![Part { text: prompt }],
+            }],
+            generation_config: GenerationConfig {
+                temperature: 0.0,
+                top_p: 0.1,
+                top_k: 1,
+                max_output_tokens: 5,
+            },
+        };
+
+        let url = self
+            .client
+            .gemini_url(&format!("v1beta/models/{}:generateContent", self.model));
+        let request = self
+            .client
+            .http()
+            .post(url)
+            .header(API_KEY_HEADER, &self.api_key)
+            .json(&request);
+        let response = self.client.send(GEMINI_API, request)?;
+        let response_body: GeminiResponse = response
+            .json()
+            .map_err(|e| Error::malformed(GEMINI_API, e.to_string()))?;
+
+        if response_body.candidates.is_empty() {
+            error!("No candidates in Gemini API response");
+            return Err(Error::malformed(GEMINI_API, "no candidates"));
+        }
+
+        let result_text = response_body.candidates[0]
+            .content
+            .parts
+            .first()
+            .map(|part| part.text.trim().to_lowercase())
+            .unwrap_or_default();
+
+        if result_text == "true" {
+            debug!(
+                "Gemini sensitivity analysis result: {}, text: {}",
+                result_text, text
+            );
+        }
+
+        match result_text.as_str() {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => {
+                error!("Unexpected response from Gemini API: {}", result_text);
+                // Default to treating as sensitive if we get an unexpected response
+                Ok(true)
+            }
+        }
+    }
+}
+
+impl SensitivityClassifier for GeminiClassifier {
+    fn name(&self) -> &str {
+        "gemini"
+    }
+
+    fn classify(&self, text: &str) -> Result<Verdict> {
+        let verdict = if self.analyze(text)? {
+            Verdict::sensitive(
+                format!("{} identified sensitive content", self.model),
+                self.name(),
+            )
+        } else {
+            Verdict::not_sensitive(
+                format!("{} found no sensitive content", self.model),
+                self.name(),
+            )
+        };
+        Ok(verdict)
+    }
+}
+
 /// Analyzes text for sensitive information using Google Gemini API
 ///
 /// # Arguments
@@ -61,97 +217,7 @@ struct CandidatePart {
 ///
 /// # Errors
 ///
-/// * `anyhow::Error` - If the text analysis fails
+/// * `Error` - If the text analysis fails
 pub fn analyze_text_sensitivity(text: &str) -> Result<bool> {
-    let api_key = env::var("GCP_API_KEY").context("GCP_API_KEY environment variable not set")?;
-
-    debug!("Analyzing text sensitivity with Gemini: {}", text);
-
-    let prompt = format!(
-        "Analyze the following text and determine if it contains ACTUAL sensitive information rather than just labels or UI elements. \
-        Respond with only 'true' if it contains real sensitive information, or 'false' if it doesn't.\n\n\
-        Examples of what IS sensitive:\n\
-        - Actual API keys like 'AIzaSyB3X7gtreHx9FGpA_XXXXXXXXXXXXX'\n\
-        - Real email addresses like 'john.doe@example.com'\n\
-        - Actual phone numbers like '+1-555-123-4567'\n\
-        - Real credit card numbers, personal names, addresses, etc.\n\n\
-        Examples of what is NOT sensitive:\n\
-        - Labels like 'API Key', 'Email', 'Credentials', 'Create', 'Password'\n\
-        - Button text like 'Submit', 'Login', 'Dismiss', 'View'\n\
-        - Generic terms like 'Username' or 'Authentication'\n\n\
-        Only mark as 'true' if it appears to be an actual sensitive value, not a UI element or label describing a value.\n\n\
-        Text to analyze: \"{}\"",
-        text
-    );
-
-    let request = GeminiRequest {
-        contents: vec![Content {
-            role: "user".to_string(),
-            parts: vec![Part { text: prompt }],
-        }],
-        generation_config: GenerationConfig {
-            temperature: 0.0,
-            top_p: 0.1,
-            top_k: 1,
-            max_output_tokens: 5,
-        },
-    };
-
-    let model =
-        std::env::var("GEMINI_MODEL").unwrap_or_else(|_| "gemini-2.0-flash-lite".to_string());
-
-    let client = Client::new();
-    let response = client
-        .post(&format!(
-            "https://generativelanguage.googleapis.com/v1beta/models/{model}:generateContent?key={}",
-            api_key
-        ))
-        .json(&request)
-        .send()
-        .context("Failed to send request to Google Gemini API")?;
-
-    let response_status = response.status();
-    if !response_status.is_success() {
-        let error_text = response
-            .text()
-            .unwrap_or_else(|_| "Could not read error response".to_string());
-        error!(
-            "Gemini API request failed with status {}: {}",
-            response_status, error_text
-        );
-        anyhow::bail!("Gemini API request failed with status {}", response_status);
-    }
-
-    let response_body: GeminiResponse = response
-        .json()
-        .context("Failed to parse Google Gemini API response")?;
-
-    if response_body.candidates.is_empty() {
-        error!("No candidates in Gemini API response");
-        anyhow::bail!("No candidates in Gemini API response");
-    }
-
-    let result_text = response_body.candidates[0]
-        .content
-        .parts
-        .first()
-        .map(|part| part.text.trim().to_lowercase())
-        .unwrap_or_default();
-
-    if result_text == "true" {
-        debug!(
-            "Gemini sensitivity analysis result: {}, text: {}",
-            result_text, text
-        );
-    }
-
-    match result_text.as_str() {
-        "true" => Ok(true),
-        "false" => Ok(false),
-        _ => {
-            error!("Unexpected response from Gemini API: {}", result_text);
-            // Default to treating as sensitive if we get an unexpected response
-            Ok(true)
-        }
-    }
+    GeminiClassifier::from_env()?.analyze(text)
 }
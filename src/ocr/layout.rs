@@ -0,0 +1,523 @@
+use std::ops::Range;
+
+use super::detection::TextAnnotation;
+
+/// Axis-aligned box of a text annotation, in image coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextBox {
+    pub min_x: i32,
+    pub min_y: i32,
+    pub max_x: i32,
+    pub max_y: i32,
+}
+
+impl TextBox {
+    /// Box of an annotation, `None` if it has no bounding polygon
+    pub fn from_annotation(annotation: &TextAnnotation) -> Option<Self> {
+        let mut vertices = annotation.bounding_poly.as_ref()?.vertices.iter();
+        let first = vertices.next()?;
+        let mut text_box = Self {
+            min_x: first.x,
+            min_y: first.y,
+            max_x: first.x,
+            max_y: first.y,
+        };
+        for vertex in vertices {
+            text_box.min_x = text_box.min_x.min(vertex.x);
+            text_box.min_y = text_box.min_y.min(vertex.y);
+            text_box.max_x = text_box.max_x.max(vertex.x);
+            text_box.max_y = text_box.max_y.max(vertex.y);
+        }
+        Some(text_box)
+    }
+
+    pub fn width(&self) -> i32 {
+        self.max_x - self.min_x
+    }
+
+    pub fn height(&self) -> i32 {
+        self.max_y - self.min_y
+    }
+
+    /// Length of the vertical range shared with another box
+    pub fn vertical_overlap(&self, other: &TextBox) -> i32 {
+        (self.max_y.min(other.max_y) - self.min_y.max(other.min_y)).max(0)
+    }
+
+    /// Length of the horizontal range shared with another box
+    pub fn horizontal_overlap(&self, other: &TextBox) -> i32 {
+        (self.max_x.min(other.max_x) - self.min_x.max(other.min_x)).max(0)
+    }
+
+    /// Smallest box containing both boxes
+    pub fn union(&self, other: &TextBox) -> TextBox {
+        TextBox {
+            min_x: self.min_x.min(other.min_x),
+            min_y: self.min_y.min(other.min_y),
+            max_x: self.max_x.max(other.max_x),
+            max_y: self.max_y.max(other.max_y),
+        }
+    }
+}
+
+/// A word of a [`TextLine`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineWord {
+    /// Index of the word in the annotations the line was built from
+    pub annotation: usize,
+    /// Byte range of the word in the line text
+    pub range: Range<usize>,
+    pub bounds: TextBox,
+}
+
+/// Words on the same line of text, in reading order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextLine {
+    /// The words joined with single spaces
+    pub text: String,
+    pub words: Vec<LineWord>,
+    pub bounds: TextBox,
+}
+
+impl TextLine {
+    /// The words of the line overlapping a byte range of the line text
+    pub fn words_in(&self, range: &Range<usize>) -> impl Iterator<Item = &LineWord> {
+        self.words
+            .iter()
+            .filter(move |word| word.range.start < range.end && range.start < word.range.end)
+    }
+}
+
+/// Group text annotations into lines using their geometry
+///
+/// Words belong to the same line when they overlap vertically by at least
+/// half of the smaller height. A horizontal gap wider than twice the line
+/// height, such as between table columns, starts a new line. The leading
+/// whole-text annotation is skipped when there are several annotations.
+///
+/// # Arguments
+///
+/// * `annotations` - The detected text annotations
+///
+/// # Returns
+///
+/// * `Vec<TextLine>` - The lines, from top to bottom, and left to right on the same row
+///
+pub fn group_lines(annotations: &[TextAnnotation]) -> Vec<TextLine> {
+    let skipped = usize::from(annotations.len() > 1);
+    let mut words: Vec<(usize, TextBox)> = annotations
+        .iter()
+        .enumerate()
+        .skip(skipped)
+        .filter(|(_, annotation)| !annotation.description.trim().is_empty())
+        .filter_map(|(index, annotation)| Some((index, TextBox::from_annotation(annotation)?)))
+        .collect();
+    words.sort_by_key(|(_, bounds)| bounds.min_y + bounds.max_y);
+
+    // Rows of words sharing the same vertical range
+    let mut rows: Vec<(TextBox, Vec<(usize, TextBox)>)> = Vec::new();
+    for (index, bounds) in words {
+        let row = rows.iter_mut().rev().find(|(row_box, _)| {
+            row_box.vertical_overlap(&bounds) * 2 >= row_box.height().min(bounds.height())
+        });
+        match row {
+            Some((row_box, row_words)) => {
+                row_box.min_y = row_box.min_y.min(bounds.min_y);
+                row_box.max_y = row_box.max_y.max(bounds.max_y);
+                row_words.push((index, bounds));
+            }
+            None => rows.push((bounds, vec![(index, bounds)])),
+        }
+    }
+
+    let mut lines = Vec::new();
+    for (row_box, mut row_words) in rows {
+        row_words.sort_by_key(|(_, bounds)| bounds.min_x);
+        let max_gap = row_box.height().max(1) * 2;
+
+        let mut line: Option<TextLine> = None;
+        for (index, bounds) in row_words {
+            let description = annotations[index].description.trim();
+            match &mut line {
+                Some(current) if bounds.min_x - current.bounds.max_x <= max_gap => {
+                    current.text.push(' ');
+                    let start = current.text.len();
+                    current.text.push_str(description);
+                    current.words.push(LineWord {
+                        annotation: index,
+                        range: start..current.text.len(),
+                        bounds,
+                    });
+                    current.bounds.min_y = current.bounds.min_y.min(bounds.min_y);
+                    current.bounds.max_y = current.bounds.max_y.max(bounds.max_y);
+                    current.bounds.max_x = current.bounds.max_x.max(bounds.max_x);
+                }
+                _ => {
+                    lines.extend(line.take());
+                    line = Some(TextLine {
+                        text: description.to_string(),
+                        words: vec![LineWord {
+                            annotation: index,
+                            range: 0..description.len(),
+                            bounds,
+                        }],
+                        bounds,
+                    });
+                }
+            }
+        }
+        lines.extend(line);
+    }
+    lines
+}
+
+/// Labels whose values are masked by default, see [`find_labeled_values`]
+///
+/// Every label ends with a colon so that it only labels a value when
+/// followed by one: the same words are also menu entries, table headers and
+/// button texts, next to text that is no value.
+pub const DEFAULT_VALUE_LABELS: &[&str] = &[
+    "password:",
+    "passcode:",
+    "passphrase:",
+    "api key:",
+    "api secret:",
+    "access key:",
+    "secret key:",
+    "client secret:",
+    "secret:",
+    "access token:",
+    "token:",
+    "private key:",
+    "email:",
+    "e-mail:",
+    "email address:",
+    "phone:",
+    "phone number:",
+];
+
+/// Farthest a value to the right of its label may be, in label heights
+const MAX_VALUE_GAP: i32 = 6;
+
+/// A label as matched against the words of a line
+struct Label {
+    /// The label without its trailing colon
+    name: String,
+    /// Lowercase words of the label
+    words: Vec<String>,
+    /// Whether the label must be followed by a colon
+    colon: bool,
+}
+
+/// Parse labels, longest first so that "api key" is preferred to "key"
+///
+/// A label ending with a colon only matches when followed by a colon.
+fn parse_labels(labels: &[String]) -> Vec<Label> {
+    let mut labels: Vec<Label> = labels
+        .iter()
+        .map(|label| {
+            let label = label.trim();
+            let name = label.trim_end_matches(':').trim_end();
+            Label {
+                name: name.to_string(),
+                words: name.split_whitespace().map(str::to_lowercase).collect(),
+                colon: name.len() < label.len(),
+            }
+        })
+        .filter(|label| !label.words.is_empty())
+        .collect();
+    labels.sort_by_key(|label| std::cmp::Reverse(label.words.len()));
+    labels
+}
+
+/// Texts of the words of a line
+fn word_texts(line: &TextLine) -> Vec<&str> {
+    line.words
+        .iter()
+        .map(|word| &line.text[word.range.clone()])
+        .collect()
+}
+
+/// Number of words of a line matching a label at its start, with the colon
+/// ending the label, `None` if the line does not start with the label
+fn label_prefix(words: &[&str], label: &[String]) -> Option<(usize, bool)> {
+    if words.len() < label.len() {
+        return None;
+    }
+    let last = label.len() - 1;
+    for (index, expected) in label.iter().enumerate() {
+        let word = words[index].to_lowercase();
+        let word = if index == last {
+            word.trim_end_matches(':')
+        } else {
+            &word
+        };
+        if word != expected {
+            return None;
+        }
+    }
+
+    if words[last].ends_with(':') {
+        Some((label.len(), true))
+    } else if words.get(label.len()) == Some(&":") {
+        Some((label.len() + 1, true))
+    } else {
+        Some((label.len(), false))
+    }
+}
+
+/// Find the values of form fields whose label is known
+///
+/// A line starting with a label followed by a colon labels the rest of the
+/// line. A line holding only the label, followed by a colon when the label
+/// ends with one, labels the nearest line to its right on the same row, within six label heights, or
+/// else the line directly below it. Lines that are labels themselves are
+/// never taken as values.
+///
+/// # Arguments
+///
+/// * `annotations` - The detected text annotations
+/// * `labels` - The known labels, matched ignoring case, those ending with a
+///   colon only when followed by one
+///
+/// # Returns
+///
+/// * `Vec<(usize, String)>` - Index of each value annotation and the label it belongs to
+///
+pub fn find_labeled_values(
+    annotations: &[TextAnnotation],
+    labels: &[String],
+) -> Vec<(usize, String)> {
+    let labels = parse_labels(labels);
+    let lines = group_lines(annotations);
+    let find_label = |line: &TextLine| {
+        let words = word_texts(line);
+        labels.iter().find_map(|label| {
+            label_prefix(&words, &label.words)
+                .filter(|(_, colon)| *colon || !label.colon)
+                .map(|(length, colon)| (&label.name, length, colon))
+        })
+    };
+
+    let mut values = Vec::new();
+    for line in &lines {
+        let Some((label, length, colon)) = find_label(line) else {
+            continue;
+        };
+        let rest = &line.words[length..];
+        if !rest.is_empty() {
+            // Without a colon, the label is just a word of a sentence or a button
+            if colon {
+                values.extend(rest.iter().map(|word| (word.annotation, label.clone())));
+            }
+            continue;
+        }
+
+        let height = line.bounds.height().max(1);
+        let right = lines
+            .iter()
+            .filter(|other| {
+                other.bounds.min_x >= line.bounds.max_x
+                    && other.bounds.min_x - line.bounds.max_x <= height * MAX_VALUE_GAP
+                    && other.bounds.vertical_overlap(&line.bounds) * 2
+                        >= other.bounds.height().min(height)
+            })
+            .min_by_key(|other| other.bounds.min_x);
+        let below = || {
+            lines
+                .iter()
+                .filter(|other| {
+                    other.bounds.min_y >= line.bounds.min_y + height / 2
+                        && other.bounds.min_y - line.bounds.max_y <= height * 2
+                        && other.bounds.horizontal_overlap(&line.bounds) > 0
+                })
+                .min_by_key(|other| other.bounds.min_y)
+        };
+        if let Some(value) = right
+            .or_else(below)
+            .filter(|value| find_label(value).is_none())
+        {
+            values.extend(
+                value
+                    .words
+                    .iter()
+                    .map(|word| (word.annotation, label.clone())),
+            );
+        }
+    }
+    values
+}
+
+/// Common column headers of customer tables, for [`find_column_cells`]
+///
+/// Column masking is opt-in: these are not enabled by default.
+pub const COMMON_COLUMN_HEADERS: &[&str] = &[
+    "email",
+    "e-mail",
+    "email address",
+    "phone",
+    "phone number",
+    "mobile",
+    "customer",
+    "customer name",
+];
+
+/// A known header found in a line, with the words it covers
+struct Header<'a> {
+    name: &'a String,
+    words: &'a [LineWord],
+    bounds: TextBox,
+}
+
+/// Whether the words of a line from `start` to `end` stand apart from the
+/// other words of the line, as table headers do, by at least `gap` pixels
+fn stands_apart(line: &TextLine, start: usize, end: usize, gap: i32) -> bool {
+    let before = start.checked_sub(1).is_none_or(|previous| {
+        line.words[start].bounds.min_x - line.words[previous].bounds.max_x >= gap
+    });
+    let after = line
+        .words
+        .get(end)
+        .is_none_or(|next| next.bounds.min_x - line.words[end - 1].bounds.max_x >= gap);
+    before && after
+}
+
+/// Find the cells of table columns whose header is known
+///
+/// A header is a run of words of a line matching a known header, set apart
+/// from the other words of its line by at least its height. A header row
+/// must hold at least two headers, so that a header word in a paragraph is
+/// not taken for a table. The words below a header belong to its column
+/// when they overlap it horizontally more than any other word of the header
+/// row. The column ends at the first vertical gap between its cells larger
+/// than three times the header height, and is only masked when it has at
+/// least two rows.
+///
+/// # Arguments
+///
+/// * `annotations` - The detected text annotations
+/// * `headers` - The known headers, matched ignoring case
+///
+/// # Returns
+///
+/// * `Vec<(usize, String)>` - Index of each cell annotation and the header of its column
+///
+pub fn find_column_cells(
+    annotations: &[TextAnnotation],
+    headers: &[String],
+) -> Vec<(usize, String)> {
+    let known = parse_labels(headers);
+    if known.is_empty() {
+        return Vec::new();
+    }
+
+    let lines = group_lines(annotations);
+    let mut words: Vec<&LineWord> = lines.iter().flat_map(|line| &line.words).collect();
+    words.sort_by_key(|word| word.bounds.min_y);
+
+    let mut headers = Vec::new();
+    for line in &lines {
+        let texts = word_texts(line);
+        let mut start = 0;
+        while start < texts.len() {
+            let found = known.iter().find_map(|header| {
+                label_prefix(&texts[start..], &header.words)
+                    .map(|(length, _)| (&header.name, length))
+            });
+            let Some((name, length)) = found else {
+                start += 1;
+                continue;
+            };
+
+            let header_words = &line.words[start..start + length];
+            let bounds = header_words
+                .iter()
+                .skip(1)
+                .fold(header_words[0].bounds, |bounds, word| {
+                    bounds.union(&word.bounds)
+                });
+            if stands_apart(line, start, start + length, bounds.height().max(1)) {
+                headers.push(Header {
+                    name,
+                    words: header_words,
+                    bounds,
+                });
+            }
+            start += length;
+        }
+    }
+
+    let mut cells = Vec::new();
+    for header in &headers {
+        let header_box = header.bounds;
+        let height = header_box.height().max(1);
+        let same_row = |bounds: &TextBox| {
+            bounds.vertical_overlap(&header_box) * 2 >= bounds.height().min(height)
+        };
+        if headers
+            .iter()
+            .filter(|other| same_row(&other.bounds))
+            .count()
+            < 2
+        {
+            continue;
+        }
+
+        let is_header = |word: &&LineWord| {
+            header
+                .words
+                .iter()
+                .any(|header| header.annotation == word.annotation)
+        };
+
+        // The other words of the header row, the columns the cells may belong to
+        let row: Vec<&LineWord> = words
+            .iter()
+            .copied()
+            .filter(|word| same_row(&word.bounds) && !is_header(word))
+            .collect();
+
+        let mut column = Vec::new();
+        let mut bottom = header_box.max_y;
+        for word in words
+            .iter()
+            .filter(|word| word.bounds.min_y >= header_box.min_y + height / 2)
+        {
+            if row.iter().any(|other| other.annotation == word.annotation) || is_header(word) {
+                continue;
+            }
+            if word.bounds.min_y - bottom > height * 3 {
+                break;
+            }
+
+            let overlap = word.bounds.horizontal_overlap(&header_box);
+            if overlap > 0
+                && row
+                    .iter()
+                    .all(|other| word.bounds.horizontal_overlap(&other.bounds) <= overlap)
+            {
+                bottom = bottom.max(word.bounds.max_y);
+                column.push(word);
+            }
+        }
+
+        let rows = column
+            .iter()
+            .map(|word| word.bounds.min_y)
+            .fold(Vec::new(), |mut rows: Vec<i32>, min_y| {
+                if rows.last().is_none_or(|last| min_y - last > height / 2) {
+                    rows.push(min_y);
+                }
+                rows
+            })
+            .len();
+        if rows >= 2 {
+            cells.extend(
+                column
+                    .iter()
+                    .map(|word| (word.annotation, header.name.clone())),
+            );
+        }
+    }
+    cells
+}
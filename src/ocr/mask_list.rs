@@ -0,0 +1,411 @@
+use aho_corasick::{AhoCorasick, MatchKind};
+use regex::Regex;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::OnceLock;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::canonical_combining_class;
+
+use super::classifier::Verdict;
+use super::masking::{ADDITIONAL_MASKS_SOURCE, HASHED_SECRETS_SOURCE, MASK_PATTERNS_SOURCE};
+use super::secret_hashes::SecretHashes;
+use crate::error::{Error, Result};
+
+/// A named regular expression whose matches are always masked
+#[derive(Debug, Clone)]
+pub struct MaskPattern {
+    /// Name shown in reports, the pattern itself when not named
+    pub name: String,
+    pub regex: Regex,
+}
+
+impl MaskPattern {
+    /// Compile a named pattern
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Config` - If the pattern is not a valid regular expression
+    ///
+    pub fn new(name: impl Into<String>, pattern: &str) -> Result<Self> {
+        let name = name.into();
+        let regex = Regex::new(pattern)
+            .map_err(|e| Error::Config(format!("Invalid mask pattern '{}': {}", name, e)))?;
+        Ok(Self { name, regex })
+    }
+
+    /// Compile a pattern named after its source
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Config` - If the pattern is not a valid regular expression
+    ///
+    pub fn unnamed(pattern: &str) -> Result<Self> {
+        Self::new(pattern, pattern)
+    }
+}
+
+/// Parse a mask patterns file
+///
+/// Each line holds a pattern, optionally named with `name = pattern`. Blank
+/// lines and lines starting with `#` are ignored. A bare pattern containing
+/// `=` must be named.
+///
+/// # Arguments
+///
+/// * `contents` - The contents of the file
+///
+/// # Returns
+///
+/// * `Result<Vec<MaskPattern>>` - The compiled patterns, in file order
+///
+/// # Errors
+///
+/// * `Error::Config` - If a pattern is invalid, with its line number
+///
+pub fn parse_mask_patterns(contents: &str) -> Result<Vec<MaskPattern>> {
+    let named = Regex::new(r"^([A-Za-z_][\w.-]*)\s*=\s*(.+)$").expect("valid name pattern");
+
+    let mut patterns = Vec::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let pattern = match named.captures(line) {
+            Some(captures) => MaskPattern::new(&captures[1], &captures[2]),
+            None => MaskPattern::unnamed(line),
+        };
+        patterns.push(pattern.map_err(|e| Error::Config(format!("line {}: {}", number + 1, e)))?);
+    }
+    Ok(patterns)
+}
+
+/// Read a mask patterns file, see [`parse_mask_patterns`]
+///
+/// # Errors
+///
+/// * `Error::Io` - If the file cannot be read
+/// * `Error::Config` - If a pattern is invalid
+///
+pub fn load_mask_patterns(path: &Path) -> Result<Vec<MaskPattern>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| Error::io(format!("Failed to read mask patterns {:?}", path), e))?;
+    parse_mask_patterns(&contents)
+        .map_err(|e| Error::Config(format!("Invalid mask patterns {:?}: {}", path, e)))
+}
+
+/// Parse a mask terms file
+///
+/// Each line holds a term, surrounding whitespace is trimmed. Blank lines and
+/// lines starting with `#` are ignored.
+///
+/// # Arguments
+///
+/// * `contents` - The contents of the file
+///
+/// # Returns
+///
+/// * `Vec<String>` - The terms, in file order
+///
+pub fn parse_mask_terms(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+/// Read a mask terms file, see [`parse_mask_terms`]
+///
+/// # Errors
+///
+/// * `Error::Io` - If the file cannot be read
+///
+pub fn load_mask_terms(path: &Path) -> Result<Vec<String>> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| Error::io(format!("Failed to read mask terms {:?}", path), e))?;
+    Ok(parse_mask_terms(&contents))
+}
+
+/// Normalize a text for term matching: NFKC, then lowercase
+///
+/// Fullwidth and compatibility forms match their plain equivalents, e.g.
+/// `ＡＣＭＥ` and `ﬁle` normalize to `acme` and `file`.
+pub fn normalize_term(text: &str) -> String {
+    text.nfkc().flat_map(char::to_lowercase).collect()
+}
+
+/// A normalized text with the byte range of the original text each part comes from
+struct NormalizedText {
+    text: String,
+    /// Start in `text` and range in the original text of each normalized cluster
+    clusters: Vec<(usize, Range<usize>)>,
+}
+
+impl NormalizedText {
+    /// Normalize each base character with its combining marks, so that
+    /// matches can be mapped back to the original text
+    fn new(original: &str) -> Self {
+        let mut normalized = Self {
+            text: String::with_capacity(original.len()),
+            clusters: Vec::new(),
+        };
+        if original.is_ascii() {
+            normalized.text = original.to_ascii_lowercase();
+            return normalized;
+        }
+
+        let mut start = 0;
+        for (index, c) in original.char_indices().skip(1) {
+            if canonical_combining_class(c) == 0 {
+                normalized.push(original, start..index);
+                start = index;
+            }
+        }
+        if !original.is_empty() {
+            normalized.push(original, start..original.len());
+        }
+        normalized
+    }
+
+    fn push(&mut self, original: &str, range: Range<usize>) {
+        self.clusters.push((self.text.len(), range.clone()));
+        self.text.push_str(&normalize_term(&original[range]));
+    }
+
+    /// Range of the original text covering a range of the normalized text
+    fn original_range(&self, range: Range<usize>) -> Range<usize> {
+        if self.clusters.is_empty() {
+            // ASCII text, the bytes did not move
+            return range;
+        }
+        let cluster = |offset: usize| {
+            let index = self.clusters.partition_point(|(start, _)| *start <= offset);
+            &self.clusters[index.saturating_sub(1)].1
+        };
+        cluster(range.start).start..cluster(range.end - 1).end
+    }
+}
+
+/// Texts, secrets and patterns that are always masked, whatever the classifier decides
+///
+/// Texts are matched ignoring case and Unicode compatibility forms (NFKC),
+/// with a single automaton so that lists of thousands of terms stay fast.
+/// The automaton is built once, on the first search. Texts match anywhere,
+/// including inside words, unless whole word matching is enabled.
+#[derive(Debug, Clone, Default)]
+pub struct MaskList {
+    texts: Vec<String>,
+    /// Automaton over the normalized texts, pattern ids index `texts`,
+    /// built on the first search
+    matcher: OnceLock<Option<AhoCorasick>>,
+    /// Whether texts only match whole words
+    whole_words: bool,
+    secrets: SecretHashes,
+    patterns: Vec<MaskPattern>,
+}
+
+impl MaskList {
+    /// Create a list masking the given texts
+    ///
+    pub fn new(texts: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self::default().with_texts(texts)
+    }
+
+    /// Add texts to mask, empty texts are ignored
+    pub fn with_texts(mut self, texts: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        self.texts.extend(
+            texts
+                .into_iter()
+                .map(Into::into)
+                .filter(|text: &String| !normalize_term(text).is_empty()),
+        );
+        self.matcher = OnceLock::new();
+        self
+    }
+
+    /// Only match texts as whole words, so that `Ann` does not match `Annual`
+    pub fn with_whole_words(mut self, enabled: bool) -> Self {
+        self.whole_words = enabled;
+        self
+    }
+
+    /// Add hashed secrets to mask
+    pub fn with_secret_hashes(mut self, secrets: SecretHashes) -> Self {
+        self.secrets = self.secrets.merge(secrets);
+        self
+    }
+
+    /// Add patterns to mask
+    pub fn with_patterns(mut self, patterns: impl IntoIterator<Item = MaskPattern>) -> Self {
+        self.patterns.extend(patterns);
+        self
+    }
+
+    /// The texts to mask
+    pub fn texts(&self) -> &[String] {
+        &self.texts
+    }
+
+    /// The hashed secrets to mask
+    pub fn secrets(&self) -> &SecretHashes {
+        &self.secrets
+    }
+
+    /// The patterns to mask
+    pub fn patterns(&self) -> &[MaskPattern] {
+        &self.patterns
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.texts.is_empty() && self.secrets.is_empty() && self.patterns.is_empty()
+    }
+
+    /// Verdict of the first text, secret or pattern found in a text
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text to check
+    ///
+    /// # Returns
+    ///
+    /// * `Option<Verdict>` - A sensitive verdict naming the match, `None` if nothing matched
+    ///
+    pub fn check(&self, text: &str) -> Option<Verdict> {
+        let normalized = NormalizedText::new(text);
+        if let Some((_, index)) = self.find_texts(&normalized).into_iter().next() {
+            return Some(text_verdict(&self.texts[index]));
+        }
+        if !self.secrets.find(text).is_empty() {
+            return Some(secret_verdict());
+        }
+        self.patterns
+            .iter()
+            .find(|pattern| pattern.regex.is_match(text))
+            .map(pattern_verdict)
+    }
+
+    /// Find every occurrence of the texts, secrets and patterns
+    ///
+    /// Overlapping texts are reported once, as the longest one starting first.
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text to search
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(Range<usize>, Verdict)>` - Byte range and verdict of each match
+    ///
+    pub fn find(&self, text: &str) -> Vec<(Range<usize>, Verdict)> {
+        let mut found = Vec::new();
+        let normalized = NormalizedText::new(text);
+        for (range, index) in self.find_texts(&normalized) {
+            found.push((
+                normalized.original_range(range),
+                text_verdict(&self.texts[index]),
+            ));
+        }
+        for range in self.secrets.find(text) {
+            found.push((range, secret_verdict()));
+        }
+        for pattern in &self.patterns {
+            for matched in pattern.regex.find_iter(text).filter(|m| !m.is_empty()) {
+                found.push((matched.range(), pattern_verdict(pattern)));
+            }
+        }
+        found
+    }
+
+    /// Automaton over the normalized texts, built on the first call
+    ///
+    /// # Panics
+    ///
+    /// If the automaton would need more states than it can address, which
+    /// takes gigabytes of terms
+    ///
+    fn matcher(&self) -> Option<&AhoCorasick> {
+        self.matcher
+            .get_or_init(|| {
+                // Standard semantics allow overlapping search, so that a match
+                // rejected at a word boundary does not hide a shorter one
+                (!self.texts.is_empty()).then(|| {
+                    AhoCorasick::builder()
+                        .match_kind(MatchKind::Standard)
+                        .build(self.texts.iter().map(|text| normalize_term(text)))
+                        .expect("mask texts fit in an automaton")
+                })
+            })
+            .as_ref()
+    }
+
+    /// Leftmost longest matches of the texts in a normalized text, without overlaps
+    fn find_texts(&self, normalized: &NormalizedText) -> Vec<(Range<usize>, usize)> {
+        let Some(matcher) = self.matcher() else {
+            return Vec::new();
+        };
+        let mut matches: Vec<(Range<usize>, usize)> = matcher
+            .find_overlapping_iter(&normalized.text)
+            .filter(|matched| {
+                !self.whole_words || at_word_boundaries(&normalized.text, matched.range())
+            })
+            .map(|matched| (matched.range(), matched.pattern().as_usize()))
+            .collect();
+        matches.sort_by_key(|(range, _)| (range.start, std::cmp::Reverse(range.end)));
+
+        let mut end = 0;
+        matches.retain(|(range, _)| {
+            let kept = range.start >= end;
+            if kept {
+                end = range.end;
+            }
+            kept
+        });
+        matches
+    }
+}
+
+/// Whether a match does not continue a word on either side
+///
+/// Only the sides where the match itself starts or ends with a letter or
+/// digit are checked, so that `@corp.com` matches inside `jane@corp.com`.
+fn at_word_boundaries(text: &str, range: Range<usize>) -> bool {
+    let matched = &text[range.clone()];
+    let joined = |inside: Option<char>, outside: Option<char>| {
+        inside.is_some_and(char::is_alphanumeric) && outside.is_some_and(char::is_alphanumeric)
+    };
+    !joined(
+        matched.chars().next(),
+        text[..range.start].chars().next_back(),
+    ) && !joined(
+        matched.chars().next_back(),
+        text[range.end..].chars().next(),
+    )
+}
+
+impl From<&[String]> for MaskList {
+    fn from(texts: &[String]) -> Self {
+        Self::new(texts.iter().cloned())
+    }
+}
+
+fn text_verdict(text: &str) -> Verdict {
+    Verdict::sensitive(
+        format!("contains additional mask text '{}'", text),
+        ADDITIONAL_MASKS_SOURCE,
+    )
+}
+
+fn secret_verdict() -> Verdict {
+    Verdict::sensitive("matched a hashed secret", HASHED_SECRETS_SOURCE)
+}
+
+fn pattern_verdict(pattern: &MaskPattern) -> Verdict {
+    Verdict::sensitive(
+        format!("matched mask pattern '{}'", pattern.name),
+        MASK_PATTERNS_SOURCE,
+    )
+}
@@ -1,21 +1,43 @@
-use crate::ocr::detection::BoundingPoly;
-use anyhow::Result;
-use image::{DynamicImage, GenericImage, Rgba};
+use crate::error::Result;
+use crate::mask::{MaskRegion, MaskStyle};
+use crate::report::MaskedPart;
+use image::{DynamicImage, GenericImageView};
 use rayon::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::ops::Range;
+use std::sync::OnceLock;
 use tracing::{debug, error, info};
 
-use super::detection::TextAnnotation;
-use super::gemini::analyze_text_sensitivity;
+use super::classifier::{SensitivityClassifier, Verdict, default_classifier};
+use super::detection::{BoundingPoly, TextAnnotation, Vertex};
+use super::document::SymbolBox;
+use super::entropy::{ENTROPY_SOURCE, EntropyThresholds, find_high_entropy};
+use super::layout::{DEFAULT_VALUE_LABELS, find_column_cells, find_labeled_values, group_lines};
+use super::mask_list::MaskList;
+use super::pii::{PII_DETECTORS_SOURCE, find_pii};
+use super::secrets::{SECRET_RULES_SOURCE, SecretDetector};
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Kinds of text masked without asking the classifier
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SensitiveTextCriteria {
+    /// API keys, tokens and credentials, found with `secret_rules`
     pub api_keys: bool,
     pub emails: bool,
     pub phone_numbers: bool,
     pub credit_cards: bool,
+    /// Names preceded by a title such as `Mr` or `Dr`, other names are left to the classifier
     pub personal_names: bool,
+    /// Company names ending with a legal form such as `Ltd` or `GmbH`
     pub company_names: bool,
+    /// Tokens random enough to be secrets without a known format, `None` to disable
+    pub entropy: Option<EntropyThresholds>,
+    /// Form labels whose value is masked, such as `Password:` or `API Key:`
+    pub value_labels: Vec<String>,
+    /// Table headers whose column cells are masked, such as `Email` or `Customer`, none by default
+    pub column_headers: Vec<String>,
+    /// Rules recognizing API keys and tokens, the built-in ones by default
+    #[serde(skip)]
+    pub secret_rules: SecretDetector,
 }
 
 /// Default criteria for sensitive text
@@ -33,75 +55,88 @@ impl Default for SensitiveTextCriteria {
             credit_cards: true,
             personal_names: true,
             company_names: true,
+            entropy: Some(EntropyThresholds::default()),
+            value_labels: DEFAULT_VALUE_LABELS
+                .iter()
+                .map(|label| label.to_string())
+                .collect(),
+            column_headers: Vec::new(),
+            secret_rules: SecretDetector::default(),
         }
     }
 }
 
+/// Default criteria, built once since compiling the secret rules is costly
+fn default_criteria() -> &'static SensitiveTextCriteria {
+    static DEFAULT: OnceLock<SensitiveTextCriteria> = OnceLock::new();
+    DEFAULT.get_or_init(SensitiveTextCriteria::default)
+}
+
+/// Name recorded in verdicts for matches against the additional mask texts
+pub const ADDITIONAL_MASKS_SOURCE: &str = "additional_masks";
+/// Name recorded in verdicts for matches against the mask patterns
+pub const MASK_PATTERNS_SOURCE: &str = "mask_patterns";
+/// Name recorded in verdicts for values next to a known form label
+pub const LABELED_VALUES_SOURCE: &str = "labeled_values";
+/// Name recorded in verdicts for cells under a known table header
+pub const TABLE_COLUMNS_SOURCE: &str = "table_columns";
+/// Name recorded in verdicts for matches against the hashed secrets
+pub const HASHED_SECRETS_SOURCE: &str = "hashed_secrets";
+
 /// Check if a text is sensitive
 ///
 /// # Arguments
 ///
 /// * `text` - The text to check
 /// * `criteria` - The criteria for sensitive text
-/// * `additional_texts` - Additional texts to check
+/// * `mask_list` - Texts and patterns that are always masked
+/// * `classifier` - The classifier consulted when no local rule matches
 ///
 /// # Returns
 ///
-/// * `bool` - True if the text is sensitive, false otherwise
+/// * `Result<Verdict>` - Whether the text is sensitive, why, and what decided it
 ///
 /// # Errors
 ///
-/// * `anyhow::Error` - If the text analysis fails
+/// * `Error` - If the classifier fails
 ///
-fn is_sensitive_text(
+fn classify_text(
     text: &str,
     criteria: &SensitiveTextCriteria,
-    additional_texts: &[String],
-) -> bool {
-    // First check additional_texts for direct matches (this is fast and doesn't require API calls)
-    if additional_texts.iter().any(|t| text.contains(t)) {
+    mask_list: &MaskList,
+    classifier: &dyn SensitivityClassifier,
+) -> Result<Verdict> {
+    // First check the mask list for direct matches (this is fast and doesn't require API calls)
+    if let Some(verdict) = mask_list.check(text) {
         debug!("Text matched additional mask pattern: {}", text);
-        return true;
+        return Ok(verdict);
     }
 
     if text.len() < 3 {
-        return false;
+        return Ok(Verdict::not_sensitive("text too short", "length"));
     }
 
-    if criteria.api_keys
-        && text.len() > 20
-        && text
-            .chars()
-            .all(|c| c.is_alphanumeric() || c == '_' || c == '.' || c == '@')
-    {
-        debug!("Detected potential API key: {}", text);
-        return true;
-    }
-
-    // Call Gemini API to analyze the text
-    match analyze_text_sensitivity(text) {
-        Ok(is_sensitive) => {
-            if is_sensitive {
-                debug!("Gemini identified sensitive text: {}", text);
-                true
-            } else {
-                false
-            }
-        }
-        Err(err) => {
-            error!(
-                "Error calling Gemini API, defaulting to non-sensitive: {}",
-                err
-            );
-            // If API fails, fall back to safety and consider it sensitive if it looks like
-            // an email, phone number, or contains numeric sequences that might be cards/IDs
-            text.contains('@')
-                || text.contains('-')
-                || (text.chars().filter(|c| c.is_numeric()).count() > 8)
-        }
+    if let Some((_, verdict)) = find_local(text, criteria).into_iter().next() {
+        debug!("Detected {}: {}", verdict.reason, text);
+        return Ok(verdict);
     }
+
+    let verdict = classifier.classify(text)?;
+    if verdict.sensitive {
+        debug!(
+            "Classifier '{}' identified sensitive text ({}): {}",
+            verdict.classifier, verdict.reason, text
+        );
+    }
+    Ok(verdict)
 }
 
+/// Default maximum size of a text mask relative to the image dimensions
+///
+/// Larger boxes usually come from annotations spanning the whole image and
+/// are skipped instead of masked.
+pub const DEFAULT_MAX_BOX_RATIO: f32 = 0.5;
+
 /// Mask sensitive text in an image
 ///
 /// # Arguments
@@ -116,101 +151,623 @@ fn is_sensitive_text(
 ///
 /// # Errors
 ///
-/// * `anyhow::Error` - If the image processing fails
+/// * `Error` - If the image processing fails
 pub fn mask_text(
     image: &mut DynamicImage,
     annotations: &[TextAnnotation],
     additional_masks: &[String],
 ) -> Result<()> {
-    let criteria = SensitiveTextCriteria::default();
+    let classifier = default_classifier();
+    mask_text_with_classifier(image, annotations, additional_masks, classifier.as_ref())
+}
 
+/// Mask sensitive text in an image using a specific classifier
+///
+/// # Arguments
+///
+/// * `image` - The image to mask
+/// * `annotations` - The annotations to mask
+/// * `additional_masks` - Additional masks to check
+/// * `classifier` - The classifier deciding which annotations are sensitive
+///
+/// # Returns
+///
+/// * `Result<()>` - The result of the image processing
+///
+/// # Errors
+///
+/// * `Error` - If the image processing fails
+pub fn mask_text_with_classifier(
+    image: &mut DynamicImage,
+    annotations: &[TextAnnotation],
+    additional_masks: &[String],
+    classifier: &dyn SensitivityClassifier,
+) -> Result<()> {
     info!("Masking sensitive text in image");
 
-    // skip first annotation because it's usually the whole image text
-    // if there is only one annotation, process it
-    let annotations_to_process = if annotations.len() > 1 {
-        &annotations[1..]
-    } else {
-        annotations
-    };
-
-    // check sensitivity in parallel and collect sensitive annotations
-    let sensitive_annotations: Vec<&TextAnnotation> = annotations_to_process
-        .par_iter() // parallel iteration
-        .filter(|&annotation| {
-            is_sensitive_text(&annotation.description, &criteria, additional_masks)
-        })
-        .collect();
-
-    let masked_count = sensitive_annotations.len();
+    let mut verdicts = classify_annotations(annotations, additional_masks, classifier);
+    let mask_list = MaskList::from(additional_masks);
+    let masks = resolve_masks(
+        annotations,
+        &mut verdicts,
+        &mask_list,
+        default_criteria(),
+        classifier,
+    );
+    let mut masked_count = 0;
 
     // apply mask to sensitive annotations
     // because it's writing to the image, we avoid parallelization and process sequentially
-    for annotation in sensitive_annotations {
-        mask_annotation(image, annotation)?;
+    let (width, height) = image.dimensions();
+    for (annotation, mask) in annotations.iter().zip(masks) {
+        if mask == TextMask::Keep {
+            continue;
+        }
+        masked_count += 1;
+        let Some(region) = annotation_region(annotation, width, height, DEFAULT_MAX_BOX_RATIO)
+        else {
+            continue;
+        };
+
+        let parts = match &mask {
+            TextMask::Parts(spans) => masked_parts(annotation, &[], spans, width, height),
+            _ => Vec::new(),
+        };
+        if parts.is_empty() {
+            MaskStyle::TEXT_DEFAULT.apply(image, &region);
+        }
+        for part in &parts {
+            MaskStyle::TEXT_DEFAULT.apply(image, &part.region);
+        }
     }
 
     info!("Masked {} sensitive text regions", masked_count);
     Ok(())
 }
 
-/// Mask a text annotation in an image
+/// Find the annotations containing sensitive text
 ///
 /// # Arguments
 ///
-/// * `image` - The image to mask
-/// * `annotation` - The annotation to mask
+/// * `annotations` - The detected text annotations
+/// * `additional_masks` - Additional texts that are always masked
+/// * `classifier` - The classifier deciding which annotations are sensitive
 ///
 /// # Returns
 ///
-/// * `Result<()>` - The result of the image processing
+/// * `Vec<&TextAnnotation>` - The sensitive annotations
 ///
-/// # Errors
+pub fn find_sensitive_annotations<'a>(
+    annotations: &'a [TextAnnotation],
+    additional_masks: &[String],
+    classifier: &dyn SensitivityClassifier,
+) -> Vec<&'a TextAnnotation> {
+    classify_annotations(annotations, additional_masks, classifier)
+        .into_iter()
+        .zip(annotations)
+        .filter(|(verdict, _)| verdict.as_ref().is_some_and(|v| v.sensitive))
+        .map(|(_, annotation)| annotation)
+        .collect()
+}
+
+/// Classify every text annotation
 ///
-/// * `anyhow::Error` - If the image processing fails
-fn mask_annotation(image: &mut DynamicImage, annotation: &TextAnnotation) -> Result<()> {
-    let empty_poly = BoundingPoly { vertices: vec![] };
-    let vertices = &annotation
-        .bounding_poly
-        .as_ref()
-        .unwrap_or(&empty_poly)
-        .vertices;
+/// The first annotation is skipped when there are several, because it usually
+/// covers the whole text of the image.
+///
+/// A failing classifier is logged and its annotation treated as not sensitive.
+/// Use [`try_classify_annotations`] to handle classifier failures.
+///
+/// # Arguments
+///
+/// * `annotations` - The detected text annotations
+/// * `additional_masks` - Additional texts that are always masked
+/// * `classifier` - The classifier deciding which annotations are sensitive
+///
+/// # Returns
+///
+/// * `Vec<Option<Verdict>>` - One entry per annotation, `None` for skipped annotations
+///
+pub fn classify_annotations(
+    annotations: &[TextAnnotation],
+    additional_masks: &[String],
+    classifier: &dyn SensitivityClassifier,
+) -> Vec<Option<Verdict>> {
+    let mask_list = MaskList::from(additional_masks);
+    try_classify_annotations(annotations, &mask_list, default_criteria(), classifier)
+        .into_iter()
+        .map(|result| {
+            result.map(|verdict| {
+                verdict.unwrap_or_else(|err| {
+                    error!(
+                        "Classifier '{}' failed, defaulting to non-sensitive: {}",
+                        classifier.name(),
+                        err
+                    );
+                    Verdict::not_sensitive(format!("classifier failed: {}", err), classifier.name())
+                })
+            })
+        })
+        .collect()
+}
 
-    if vertices.is_empty() {
-        debug!("Skipping annotation with empty bounding polygon");
-        return Ok(());
-    }
+/// Classify every text annotation, keeping classifier failures
+///
+/// The first annotation is skipped when there are several, because it usually
+/// covers the whole text of the image.
+///
+/// # Arguments
+///
+/// * `annotations` - The detected text annotations
+/// * `mask_list` - Texts and patterns that are always masked
+/// * `criteria` - The kinds of text detected locally, before asking the classifier
+/// * `classifier` - The classifier deciding which annotations are sensitive
+///
+/// # Returns
+///
+/// * `Vec<Option<Result<Verdict>>>` - One entry per annotation, `None` for
+///   skipped annotations and the classifier error for failed ones
+///
+pub fn try_classify_annotations(
+    annotations: &[TextAnnotation],
+    mask_list: &MaskList,
+    criteria: &SensitiveTextCriteria,
+    classifier: &dyn SensitivityClassifier,
+) -> Vec<Option<Result<Verdict>>> {
+    // skip first annotation because it's usually the whole image text
+    // if there is only one annotation, process it
+    let skipped = if annotations.len() > 1 { 1 } else { 0 };
 
-    let min_x = vertices.iter().map(|v| v.x).min().unwrap_or(0).max(0) as u32;
-    let min_y = vertices.iter().map(|v| v.y).min().unwrap_or(0).max(0) as u32;
-    let max_x = vertices.iter().map(|v| v.x).max().unwrap_or(0).max(0) as u32;
-    let max_y = vertices.iter().map(|v| v.y).max().unwrap_or(0).max(0) as u32;
+    // check sensitivity in parallel, keeping the annotation order
+    annotations
+        .par_iter() // parallel iteration
+        .enumerate()
+        .map(|(index, annotation)| {
+            (index >= skipped)
+                .then(|| classify_text(&annotation.description, criteria, mask_list, classifier))
+        })
+        .collect()
+}
 
-    let (width, height) = (image.width(), image.height());
+/// Compute the image region covered by a text annotation
+///
+/// # Arguments
+///
+/// * `annotation` - The annotation to locate
+/// * `width` - The width of the image
+/// * `height` - The height of the image
+/// * `max_box_ratio` - Maximum box size relative to the image, larger boxes are skipped
+///
+/// # Returns
+///
+/// * `Option<MaskRegion>` - The region to mask, or `None` if the annotation
+///   has no usable bounding polygon
+///
+pub fn annotation_region(
+    annotation: &TextAnnotation,
+    width: u32,
+    height: u32,
+    max_box_ratio: f32,
+) -> Option<MaskRegion> {
+    let vertices = annotation
+        .bounding_poly
+        .as_ref()
+        .map(|poly| poly.vertices.as_slice())
+        .unwrap_or_default();
 
-    let max_x = max_x.min(width - 1);
-    let max_y = max_y.min(height - 1);
+    let Some(region) = MaskRegion::from_points(vertices.iter().map(|v| (v.x, v.y)), width, height)
+    else {
+        debug!("Skipping annotation with empty bounding polygon");
+        return None;
+    };
 
-    let box_width = max_x.saturating_sub(min_x);
-    let box_height = max_y.saturating_sub(min_y);
+    let box_width = region.max_x - region.min_x;
+    let box_height = region.max_y - region.min_y;
 
-    if box_width > width / 2 || box_height > height / 2 {
+    if box_width as f32 > width as f32 * max_box_ratio
+        || box_height as f32 > height as f32 * max_box_ratio
+    {
         debug!(
             "Skipping oversized bounding box: {}x{}",
             box_width, box_height
         );
-        return Ok(());
+        return None;
+    }
+
+    Some(region)
+}
+
+/// Locate the sensitive parts of a text classified as sensitive
+///
+/// # Arguments
+///
+/// * `text` - The classified text
+/// * `verdict` - The verdict of the text
+/// * `mask_list` - Texts and patterns that are always masked
+/// * `criteria` - The kinds of text detected locally
+/// * `classifier` - The classifier consulted for the text
+///
+/// # Returns
+///
+/// * `Vec<Range<usize>>` - Byte ranges of the sensitive parts, empty when
+///   the whole text must be masked
+///
+pub fn sensitive_spans(
+    text: &str,
+    verdict: &Verdict,
+    mask_list: &MaskList,
+    criteria: &SensitiveTextCriteria,
+    classifier: &dyn SensitivityClassifier,
+) -> Vec<Range<usize>> {
+    if !verdict.sensitive {
+        return Vec::new();
+    }
+    match verdict.classifier.as_str() {
+        ADDITIONAL_MASKS_SOURCE | MASK_PATTERNS_SOURCE | HASHED_SECRETS_SOURCE => mask_list
+            .find(text)
+            .into_iter()
+            .map(|(range, _)| range)
+            .collect(),
+        SECRET_RULES_SOURCE | PII_DETECTORS_SOURCE | ENTROPY_SOURCE => find_local(text, criteria)
+            .into_iter()
+            .filter(|(_, found)| found.classifier == verdict.classifier)
+            .map(|(range, _)| range)
+            .collect(),
+        _ => classifier.sensitive_spans(text, verdict),
     }
+}
 
-    let black = Rgba([0, 0, 0, 128]);
+/// Find the secrets, personal information and random tokens the criteria ask for,
+/// without any API call
+fn find_local(text: &str, criteria: &SensitiveTextCriteria) -> Vec<(Range<usize>, Verdict)> {
+    let mut found = Vec::new();
+    if criteria.api_keys {
+        found.extend(criteria.secret_rules.find(text));
+    }
+    found.extend(find_pii(text, criteria));
+    if let Some(thresholds) = &criteria.entropy {
+        found.extend(find_high_entropy(text, thresholds));
+    }
+    found
+}
 
-    for y in min_y..=max_y {
-        for x in min_x..=max_x {
-            if x < width && y < height {
-                image.put_pixel(x, y, black);
+/// Find the sensitive parts of a longer text, such as a line of several words
+///
+/// Searches the mask list, the parts found by
+/// [`SensitivityClassifier::find_sensitive`] and the local detectors.
+///
+/// # Arguments
+///
+/// * `text` - The text to search
+/// * `mask_list` - Texts and patterns that are always masked
+/// * `criteria` - The kinds of text detected locally
+/// * `classifier` - The classifier searching the text
+///
+/// # Returns
+///
+/// * `Vec<(Range<usize>, Verdict)>` - Byte range and verdict of each sensitive part
+///
+pub fn find_sensitive_phrases(
+    text: &str,
+    mask_list: &MaskList,
+    criteria: &SensitiveTextCriteria,
+    classifier: &dyn SensitivityClassifier,
+) -> Vec<(Range<usize>, Verdict)> {
+    let mut phrases = mask_list.find(text);
+    match classifier.find_sensitive(text) {
+        Ok(found) => phrases.extend(found),
+        Err(err) => error!(
+            "Classifier '{}' failed to search text: {}",
+            classifier.name(),
+            err
+        ),
+    }
+    phrases.extend(find_local(text, criteria));
+    phrases
+}
+
+/// What is masked in a text annotation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TextMask {
+    /// Nothing, the text is not sensitive
+    Keep,
+    /// The whole annotation
+    Whole,
+    /// The given byte ranges of the annotation text
+    Parts(Vec<Range<usize>>),
+}
+
+/// Decide what to mask in every annotation
+///
+/// Besides the annotations classified as sensitive, the annotations are
+/// grouped into lines and the sensitive phrases spanning several words of a
+/// line (a full name, a phone number split by spaces) are masked in every
+/// word they cover. Words only sensitive as part of a phrase get the verdict
+/// of the phrase. The values of known form labels (`Password: hunter2`, or a
+/// label with its value to the right or below) are masked whole.
+///
+/// # Arguments
+///
+/// * `annotations` - The detected text annotations
+/// * `verdicts` - The verdict of each annotation, updated for phrase matches
+/// * `mask_list` - Texts and patterns that are always masked
+/// * `criteria` - The kinds of text detected locally
+/// * `classifier` - The classifier that produced the verdicts
+///
+/// # Returns
+///
+/// * `Vec<TextMask>` - What to mask, one entry per annotation
+///
+pub fn resolve_masks(
+    annotations: &[TextAnnotation],
+    verdicts: &mut [Option<Verdict>],
+    mask_list: &MaskList,
+    criteria: &SensitiveTextCriteria,
+    classifier: &dyn SensitivityClassifier,
+) -> Vec<TextMask> {
+    let mut masks: Vec<TextMask> = annotations
+        .iter()
+        .zip(verdicts.iter())
+        .map(|(annotation, verdict)| match verdict {
+            Some(verdict) if verdict.sensitive => {
+                let spans = sensitive_spans(
+                    &annotation.description,
+                    verdict,
+                    mask_list,
+                    criteria,
+                    classifier,
+                );
+                if spans.is_empty() {
+                    TextMask::Whole
+                } else {
+                    TextMask::Parts(spans)
+                }
+            }
+            _ => TextMask::Keep,
+        })
+        .collect();
+
+    for line in group_lines(annotations) {
+        for (range, verdict) in find_sensitive_phrases(&line.text, mask_list, criteria, classifier)
+        {
+            let words: Vec<_> = line.words_in(&range).collect();
+            // Phrases within a single word were found when classifying it
+            if words.len() < 2 {
+                continue;
+            }
+            debug!("Phrase spanning {} words is sensitive", words.len());
+
+            for word in words {
+                // Line text holds the words without surrounding whitespace
+                let description = &annotations[word.annotation].description;
+                let offset = description.len() - description.trim_start().len();
+                let start = range.start.max(word.range.start) - word.range.start + offset;
+                let end = range.end.min(word.range.end) - word.range.start + offset;
+
+                match &mut masks[word.annotation] {
+                    TextMask::Whole => {}
+                    TextMask::Parts(spans) => spans.push(start..end),
+                    mask @ TextMask::Keep => {
+                        *mask = TextMask::Parts(std::iter::once(start..end).collect());
+                        if let Some(slot) = verdicts.get_mut(word.annotation) {
+                            *slot = Some(verdict.clone());
+                        }
+                    }
+                }
             }
         }
     }
 
-    Ok(())
+    // The same part may be found by several phrases or detectors
+    for mask in &mut masks {
+        if let TextMask::Parts(spans) = mask {
+            spans.sort_by_key(|span| (span.start, span.end));
+            spans.dedup();
+        }
+    }
+
+    for (index, label) in find_labeled_values(annotations, &criteria.value_labels) {
+        debug!("Value of label '{}' is sensitive", label);
+        if masks[index] == TextMask::Keep
+            && let Some(slot) = verdicts.get_mut(index)
+        {
+            let reason = format!("value of label '{}'", label);
+            *slot = Some(Verdict::sensitive(reason, LABELED_VALUES_SOURCE));
+        }
+        masks[index] = TextMask::Whole;
+    }
+
+    for (index, header) in find_column_cells(annotations, &criteria.column_headers) {
+        debug!("Cell of column '{}' is sensitive", header);
+        if masks[index] == TextMask::Keep
+            && let Some(slot) = verdicts.get_mut(index)
+        {
+            let reason = format!("cell of column '{}'", header);
+            *slot = Some(Verdict::sensitive(reason, TABLE_COLUMNS_SOURCE));
+        }
+        masks[index] = TextMask::Whole;
+    }
+
+    masks
+}
+
+/// Compute the image regions covering parts of a text annotation
+///
+/// Each part is covered by the boxes of its characters when they are known,
+/// otherwise by the matching slice of the annotation box, assuming
+/// characters of equal width.
+///
+/// # Arguments
+///
+/// * `annotation` - The annotation containing the parts
+/// * `symbols` - The character boxes of the annotation, may be empty
+/// * `spans` - Byte ranges of the parts in the annotation text
+/// * `width` - The width of the image
+/// * `height` - The height of the image
+///
+/// # Returns
+///
+/// * `Vec<MaskedPart>` - One entry per part, merging overlapping parts.
+///   Empty when the parts cover the whole text or one of them cannot be
+///   located, in which case the whole annotation must be masked
+///
+pub fn masked_parts(
+    annotation: &TextAnnotation,
+    symbols: &[SymbolBox],
+    spans: &[Range<usize>],
+    width: u32,
+    height: u32,
+) -> Vec<MaskedPart> {
+    let text = annotation.description.as_str();
+    let mut spans: Vec<_> = spans.iter().filter(|s| !s.is_empty()).cloned().collect();
+    spans.sort_by_key(|span| (span.start, span.end));
+
+    let mut merged: Vec<Range<usize>> = Vec::with_capacity(spans.len());
+    for span in spans {
+        match merged.last_mut() {
+            Some(last) if span.start <= last.end => last.end = last.end.max(span.end),
+            _ => merged.push(span),
+        }
+    }
+
+    let blank = |rest: Option<&str>| rest.is_none_or(|rest| rest.trim().is_empty());
+    let covers_whole_text = match merged.as_slice() {
+        [] => true,
+        [span] => blank(text.get(..span.start)) && blank(text.get(span.end..)),
+        _ => false,
+    };
+    if covers_whole_text {
+        return Vec::new();
+    }
+
+    let mut parts = Vec::with_capacity(merged.len());
+    for span in merged {
+        let points: Vec<(i32, i32)> = symbols
+            .iter()
+            .filter(|symbol| symbol.range.start < span.end && span.start < symbol.range.end)
+            .flat_map(|symbol| &symbol.bounding_poly.vertices)
+            .map(|v| (v.x, v.y))
+            .collect();
+        let points = if points.is_empty() {
+            annotation
+                .bounding_poly
+                .as_ref()
+                .and_then(|poly| estimate_span(poly, text, &span))
+                .unwrap_or_default()
+        } else {
+            points
+        };
+
+        let Some(region) = MaskRegion::from_points(points, width, height) else {
+            debug!("Could not locate part of '{}', masking all of it", text);
+            return Vec::new();
+        };
+        parts.push(MaskedPart {
+            range: span,
+            region,
+        });
+    }
+    parts
+}
+
+/// Compute the image regions covering the characters of parts of a text annotation
+///
+/// Unlike [`masked_parts`], each character is covered by its own box, so
+/// that the gaps between characters and words stay visible. When a masked
+/// character has no box, the parts are located as by [`masked_parts`].
+///
+/// # Arguments
+///
+/// * `annotation` - The annotation containing the parts
+/// * `symbols` - The character boxes of the annotation, may be empty
+/// * `spans` - Byte ranges of the parts in the annotation text
+/// * `width` - The width of the image
+/// * `height` - The height of the image
+///
+/// # Returns
+///
+/// * `Vec<MaskedPart>` - One entry per masked character, or per part when
+///   characters cannot be located. Empty when the whole annotation must be masked
+///
+pub fn masked_characters(
+    annotation: &TextAnnotation,
+    symbols: &[SymbolBox],
+    spans: &[Range<usize>],
+    width: u32,
+    height: u32,
+) -> Vec<MaskedPart> {
+    let text = annotation.description.as_str();
+    let overlaps = |range: &Range<usize>, span: &Range<usize>| {
+        range.start < span.end && span.start < range.end
+    };
+    let located = spans.iter().all(|span| {
+        text.get(span.clone()).is_some_and(|part| {
+            part.char_indices()
+                .filter(|(_, c)| !c.is_whitespace())
+                .all(|(offset, c)| {
+                    let character = span.start + offset..span.start + offset + c.len_utf8();
+                    symbols
+                        .iter()
+                        .any(|symbol| overlaps(&symbol.range, &character))
+                })
+        })
+    });
+    if !located {
+        return masked_parts(annotation, symbols, spans, width, height);
+    }
+
+    symbols
+        .iter()
+        .filter(|symbol| spans.iter().any(|span| overlaps(&symbol.range, span)))
+        .map(|symbol| {
+            let points = symbol.bounding_poly.vertices.iter().map(|v| (v.x, v.y));
+            let region = MaskRegion::from_points(points, width, height)?;
+            Some(MaskedPart {
+                range: symbol.range.clone(),
+                region,
+            })
+        })
+        .collect::<Option<Vec<_>>>()
+        .unwrap_or_default()
+}
+
+/// Estimate the polygon of a part of a text from the polygon of the whole text
+///
+/// The vertices of Vision polygons start at the top left corner of the
+/// text, in reading direction, so the part is interpolated along the top
+/// and bottom edges.
+fn estimate_span(poly: &BoundingPoly, text: &str, span: &Range<usize>) -> Option<Vec<(i32, i32)>> {
+    let total = text.chars().count().max(1) as f32;
+    let from = text.get(..span.start)?.chars().count() as f32 / total;
+    let to = text.get(..span.end)?.chars().count() as f32 / total;
+
+    let point = |v: &Vertex| (v.x as f32, v.y as f32);
+    let [top_left, top_right, bottom_right, bottom_left] = match poly.vertices.as_slice() {
+        [a, b, c, d] => [point(a), point(b), point(c), point(d)],
+        vertices => {
+            let region =
+                MaskRegion::from_points(vertices.iter().map(|v| (v.x, v.y)), u32::MAX, u32::MAX)?;
+            let (min_x, min_y) = (region.min_x as f32, region.min_y as f32);
+            let (max_x, max_y) = (region.max_x as f32, region.max_y as f32);
+            [
+                (min_x, min_y),
+                (max_x, min_y),
+                (max_x, max_y),
+                (min_x, max_y),
+            ]
+        }
+    };
+
+    let lerp = |(x0, y0): (f32, f32), (x1, y1): (f32, f32), t: f32| {
+        (
+            (x0 + (x1 - x0) * t).round() as i32,
+            (y0 + (y1 - y0) * t).round() as i32,
+        )
+    };
+    Some(vec![
+        lerp(top_left, top_right, from),
+        lerp(top_left, top_right, to),
+        lerp(bottom_left, bottom_right, to),
+        lerp(bottom_left, bottom_right, from),
+    ])
 }
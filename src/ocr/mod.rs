@@ -1,3 +1,11 @@
+pub mod classifier;
 pub mod detection;
+pub mod document;
+pub mod entropy;
 pub mod gemini;
+pub mod layout;
+pub mod mask_list;
 pub mod masking;
+pub mod pii;
+pub mod secret_hashes;
+pub mod secrets;
@@ -0,0 +1,150 @@
+use regex::Regex;
+use std::ops::Range;
+use std::sync::OnceLock;
+
+use super::classifier::Verdict;
+use super::masking::SensitiveTextCriteria;
+
+/// Name recorded in verdicts for the local personal information detectors
+pub const PII_DETECTORS_SOURCE: &str = "pii_detectors";
+
+/// Kind of personal information found by the local detectors
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PiiKind {
+    Email,
+    PhoneNumber,
+    CreditCard,
+    PersonalName,
+    CompanyName,
+}
+
+impl PiiKind {
+    const ALL: [PiiKind; 5] = [
+        Self::Email,
+        Self::PhoneNumber,
+        Self::CreditCard,
+        Self::PersonalName,
+        Self::CompanyName,
+    ];
+
+    /// Whether the criteria ask for this kind to be masked
+    pub fn enabled(self, criteria: &SensitiveTextCriteria) -> bool {
+        match self {
+            Self::Email => criteria.emails,
+            Self::PhoneNumber => criteria.phone_numbers,
+            Self::CreditCard => criteria.credit_cards,
+            Self::PersonalName => criteria.personal_names,
+            Self::CompanyName => criteria.company_names,
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Self::Email => "email address",
+            Self::PhoneNumber => "phone number",
+            Self::CreditCard => "credit card number",
+            Self::PersonalName => "personal name",
+            Self::CompanyName => "company name",
+        }
+    }
+
+    fn pattern(self) -> &'static Regex {
+        static PATTERNS: OnceLock<[Regex; 5]> = OnceLock::new();
+        let patterns = PATTERNS.get_or_init(|| {
+            [
+                // RFC 5322 dot-atom local part, dot separated domain labels
+                r"[A-Za-z0-9!#$%&'*+/=?^_`{|}~-]+(?:\.[A-Za-z0-9!#$%&'*+/=?^_`{|}~-]+)*@(?:[A-Za-z0-9](?:[A-Za-z0-9-]{0,61}[A-Za-z0-9])?\.)+[A-Za-z]{2,63}",
+                // E.164 with optional grouping, or a national number with an
+                // area code in parentheses or followed by a separator
+                r"\+\d(?:[ .-]?\(?\d+\)?)+|(?:\(\d{2,5}\) ?|\b\d{2,5}[ .-])\d{3,4}[ .-]\d{3,4}\b",
+                r"\b\d(?:[ -]?\d){12,18}\b",
+                r"\b(?:Mr|Mrs|Ms|Miss|Mx|Dr|Prof)\.? \p{Lu}[\p{L}'-]+(?: \p{Lu}[\p{L}'-]+){0,2}",
+                r"\b\p{Lu}[\p{L}&'-]*(?: \p{Lu}[\p{L}&'-]*){0,3},? (?:Inc|Corp|Corporation|LLC|Ltd|Limited|GmbH|AG|plc|PLC|S\.A|SAS|Co)\b\.?",
+            ]
+            .map(|pattern| Regex::new(pattern).expect("built-in detector must compile"))
+        });
+        &patterns[self as usize]
+    }
+
+    /// Checks that the pattern cannot express
+    fn is_valid(self, text: &str, range: Range<usize>) -> bool {
+        let matched = &text[range.clone()];
+        let digits: Vec<u32> = matched.chars().filter_map(|c| c.to_digit(10)).collect();
+        match self {
+            Self::PhoneNumber if matched.starts_with('+') => (8..=15).contains(&digits.len()),
+            Self::PhoneNumber => {
+                (9..=11).contains(&digits.len())
+                    && is_standalone(text, range)
+                    && dotted_throughout(matched)
+            }
+            Self::CreditCard => luhn(&digits),
+            _ => true,
+        }
+    }
+}
+
+/// Whether a number is not part of a longer run of numbers, such as an IP
+/// address or a version
+fn is_standalone(text: &str, range: Range<usize>) -> bool {
+    const JOINERS: &[char] = &['.', '-', '/', ':'];
+    let before = text[..range.start].chars().next_back();
+    let mut after = text[range.end..].chars();
+    let joined_before = before.is_some_and(|c| c.is_alphanumeric() || JOINERS.contains(&c));
+    let joined_after = after.next().is_some_and(|c| JOINERS.contains(&c))
+        && after.next().is_some_and(|c| c.is_ascii_digit());
+    !joined_before && !joined_after
+}
+
+/// Whether a number uses dots either between all of its groups or not at all
+fn dotted_throughout(number: &str) -> bool {
+    let separators: Vec<char> = number
+        .chars()
+        .filter(|c| !c.is_ascii_digit() && !matches!(c, '(' | ')'))
+        .collect();
+    !separators.contains(&'.') || separators.iter().all(|&c| c == '.')
+}
+
+/// Luhn checksum of a card number
+fn luhn(digits: &[u32]) -> bool {
+    let sum: u32 = digits
+        .iter()
+        .rev()
+        .enumerate()
+        .map(|(index, &digit)| match (index % 2, digit * 2) {
+            (0, _) => digit,
+            (_, doubled) if doubled > 9 => doubled - 9,
+            (_, doubled) => doubled,
+        })
+        .sum();
+    (13..=19).contains(&digits.len()) && sum.is_multiple_of(10)
+}
+
+/// Find the personal information the criteria ask for, without any API call
+///
+/// # Arguments
+///
+/// * `text` - The text to search
+/// * `criteria` - The kinds of sensitive text to look for
+///
+/// # Returns
+///
+/// * `Vec<(Range<usize>, Verdict)>` - Byte range and verdict of each match
+///
+pub fn find_pii(text: &str, criteria: &SensitiveTextCriteria) -> Vec<(Range<usize>, Verdict)> {
+    let mut found = Vec::new();
+    for kind in PiiKind::ALL
+        .into_iter()
+        .filter(|kind| kind.enabled(criteria))
+    {
+        for matched in kind.pattern().find_iter(text) {
+            if kind.is_valid(text, matched.range()) {
+                let reason = format!("detected {}", kind.description());
+                found.push((
+                    matched.range(),
+                    Verdict::sensitive(reason, PII_DETECTORS_SOURCE),
+                ));
+            }
+        }
+    }
+    found
+}
@@ -0,0 +1,253 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+
+/// Longest run of words hashed together, for secrets split by the OCR
+pub const MAX_NGRAM_WORDS: usize = 4;
+
+/// Characters separating a secret from a label or quotes in the same word
+const DELIMITERS: &[char] = &[
+    ':', ',', ';', '"', '\'', '`', '(', ')', '[', ']', '{', '}', '<', '>',
+];
+
+/// Hex encoded SHA-256 digest of a salted secret, as listed in a secret hashes file
+///
+/// # Arguments
+///
+/// * `salt` - The salt of the file, prepended to the secret
+/// * `secret` - The secret
+///
+/// # Returns
+///
+/// * `String` - The lowercase hex digest of `salt` followed by `secret`
+///
+pub fn hash_secret(salt: &str, secret: &str) -> String {
+    digest(salt, secret)
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn digest(salt: &str, candidate: &str) -> [u8; 32] {
+    Sha256::new()
+        .chain_update(salt)
+        .chain_update(candidate)
+        .finalize()
+        .into()
+}
+
+fn decode_digest(hex: &str) -> Option<[u8; 32]> {
+    if hex.len() != 64 || !hex.is_ascii() {
+        return None;
+    }
+    let mut digest = [0; 32];
+    for (byte, pair) in digest.iter_mut().zip(hex.as_bytes().chunks(2)) {
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(digest)
+}
+
+/// Salted SHA-256 digests of secrets to mask, without their plaintext
+///
+/// Candidate tokens of a text are hashed with each salt and looked up: every
+/// word, the parts of a word around label separators such as `:` or `=`, and
+/// runs of up to [`MAX_NGRAM_WORDS`] words joined with a space or with nothing.
+#[derive(Debug, Clone, Default)]
+pub struct SecretHashes {
+    /// Digests grouped by the salt they were computed with
+    groups: Vec<(String, HashSet<[u8; 32]>)>,
+}
+
+impl SecretHashes {
+    /// Add the digests computed with a salt
+    pub fn with_digests(mut self, salt: impl Into<String>, digests: HashSet<[u8; 32]>) -> Self {
+        let salt = salt.into();
+        match self.groups.iter_mut().find(|(s, _)| *s == salt) {
+            Some((_, existing)) => existing.extend(digests),
+            None => self.groups.push((salt, digests)),
+        }
+        self
+    }
+
+    /// Add the digests of another list
+    pub fn merge(self, other: SecretHashes) -> Self {
+        other
+            .groups
+            .into_iter()
+            .fold(self, |merged, (salt, digests)| {
+                merged.with_digests(salt, digests)
+            })
+    }
+
+    /// Number of digests
+    pub fn len(&self) -> usize {
+        self.groups.iter().map(|(_, digests)| digests.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn contains(&self, candidate: &str) -> bool {
+        !candidate.is_empty()
+            && self
+                .groups
+                .iter()
+                .any(|(salt, digests)| digests.contains(&digest(salt, candidate)))
+    }
+
+    /// Find the secrets in a text
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text to search
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<Range<usize>>` - Byte ranges of the matching candidates, in order
+    ///
+    pub fn find(&self, text: &str) -> Vec<Range<usize>> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+
+        let words = word_ranges(text);
+        let mut found = Vec::new();
+        for (index, word) in words.iter().enumerate() {
+            found.extend(
+                word_candidates(text, word.clone())
+                    .into_iter()
+                    .filter(|range| self.contains(&text[range.clone()])),
+            );
+
+            for count in 2..=MAX_NGRAM_WORDS.min(words.len() - index) {
+                let run = &words[index..index + count];
+                let parts: Vec<_> = run.iter().map(|range| &text[range.clone()]).collect();
+                if self.contains(&parts.join(" ")) || self.contains(&parts.concat()) {
+                    found.push(word.start..run[count - 1].end);
+                }
+            }
+        }
+        found.sort_by_key(|range| (range.start, range.end));
+        found.dedup();
+        found
+    }
+}
+
+/// Byte ranges of the whitespace separated words of a text
+fn word_ranges(text: &str) -> Vec<Range<usize>> {
+    let mut words = Vec::new();
+    let mut start = None;
+    for (index, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(word_start)) => {
+                words.push(word_start..index);
+                start = None;
+            }
+            (false, None) => start = Some(index),
+            _ => {}
+        }
+    }
+    words.extend(start.map(|word_start| word_start..text.len()));
+    words
+}
+
+/// The word, its parts between delimiters and the value of `key=value` parts
+fn word_candidates(text: &str, word: Range<usize>) -> Vec<Range<usize>> {
+    let mut candidates = vec![word.clone()];
+    let mut start = word.start;
+    for (offset, c) in text[word.clone()]
+        .char_indices()
+        .chain(std::iter::once((word.len(), ' ')))
+    {
+        if offset != word.len() && !DELIMITERS.contains(&c) {
+            continue;
+        }
+        let end = word.start + offset;
+        if start < end {
+            candidates.push(start..end);
+            if let Some(equals) = text[start..end].find('=') {
+                candidates.push(start + equals + 1..end);
+            }
+        }
+        start = end + c.len_utf8();
+    }
+    candidates.dedup();
+    candidates
+}
+
+/// Parse a secret hashes file
+///
+/// A `salt = <salt>` line sets the salt of the digests that follow it. Every
+/// other line holds the hex SHA-256 digest of the salt followed by a secret,
+/// see [`hash_secret`]. Blank lines and lines starting with `#` are ignored.
+///
+/// # Arguments
+///
+/// * `contents` - The contents of the file
+///
+/// # Returns
+///
+/// * `Result<SecretHashes>` - The digests of the file
+///
+/// # Errors
+///
+/// * `Error::Config` - If a digest is invalid or comes before any salt, with its line number
+///
+pub fn parse_secret_hashes(contents: &str) -> Result<SecretHashes> {
+    let mut hashes = SecretHashes::default();
+    let mut salt: Option<String> = None;
+    let mut digests = HashSet::new();
+    for (number, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(value) = line
+            .strip_prefix("salt")
+            .and_then(|rest| rest.trim_start().strip_prefix('='))
+        {
+            if let Some(previous) = salt.replace(value.trim().to_string()) {
+                hashes = hashes.with_digests(previous, std::mem::take(&mut digests));
+            }
+            continue;
+        }
+
+        if salt.is_none() {
+            return Err(Error::Config(format!(
+                "line {}: digest before any 'salt = ' line",
+                number + 1
+            )));
+        }
+        let digest = decode_digest(line).ok_or_else(|| {
+            Error::Config(format!(
+                "line {}: expected a hex SHA-256 digest of 64 characters",
+                number + 1
+            ))
+        })?;
+        digests.insert(digest);
+    }
+    if let Some(salt) = salt {
+        hashes = hashes.with_digests(salt, digests);
+    }
+    Ok(hashes)
+}
+
+/// Read a secret hashes file, see [`parse_secret_hashes`]
+///
+/// # Errors
+///
+/// * `Error::Io` - If the file cannot be read
+/// * `Error::Config` - If the file is invalid
+///
+pub fn load_secret_hashes(path: &Path) -> Result<SecretHashes> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| Error::io(format!("Failed to read secret hashes {:?}", path), e))?;
+    parse_secret_hashes(&contents)
+        .map_err(|e| Error::Config(format!("Invalid secret hashes {:?}: {}", path, e)))
+}
@@ -0,0 +1,497 @@
+use regex::Regex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::OnceLock;
+use tracing::{debug, warn};
+
+use super::classifier::Verdict;
+use crate::error::{Error, Result};
+
+/// Name recorded in verdicts for matches against the secret rules
+pub const SECRET_RULES_SOURCE: &str = "secret_rules";
+
+/// Built-in rules, named after their gitleaks counterparts
+///
+/// Each entry is the rule id, its pattern and the capture group holding the
+/// secret (0 for the whole match).
+const BUILTIN_RULES: &[(&str, &str, usize)] = &[
+    (
+        "aws-access-token",
+        r"\b(?:A3T[A-Z0-9]|AKIA|ASIA|ABIA|ACCA)[A-Z2-7]{16}\b",
+        0,
+    ),
+    (
+        "aws-secret-access-key",
+        r"(?i)aws_?secret_?(?:access_?)?key\W{0,5}([A-Za-z0-9/+=]{40})\b",
+        1,
+    ),
+    ("gcp-api-key", r"\bAIza[\w-]{35}", 0),
+    ("github-pat", r"\bghp_[0-9A-Za-z]{36}\b", 0),
+    ("github-oauth", r"\bgho_[0-9A-Za-z]{36}\b", 0),
+    ("github-app-token", r"\b(?:ghu|ghs)_[0-9A-Za-z]{36}\b", 0),
+    ("github-fine-grained-pat", r"\bgithub_pat_\w{82}\b", 0),
+    ("slack-token", r"\bxox[abposer]-[0-9A-Za-z-]{10,}", 0),
+    (
+        "slack-webhook-url",
+        r"https://hooks\.slack\.com/(?:services|workflows|triggers)/[A-Za-z0-9+/_-]{20,}",
+        0,
+    ),
+    (
+        "stripe-access-token",
+        r"\b(?:sk|rk|pk)_(?:test|live|prod)_[0-9A-Za-z]{10,99}\b",
+        0,
+    ),
+    (
+        "jwt",
+        r"\beyJ[A-Za-z0-9_-]{10,}\.eyJ[A-Za-z0-9_-]{10,}\.[A-Za-z0-9_-]{10,}={0,2}",
+        0,
+    ),
+    (
+        "private-key",
+        r"-----BEGIN[ A-Z0-9_-]{0,100}PRIVATE KEY(?: BLOCK)?-----",
+        0,
+    ),
+    (
+        "connection-string-credentials",
+        r"\b[a-z][a-z0-9+.-]*://[^\s:/@]+:([^\s@/]+)@[^\s/]+",
+        1,
+    ),
+    (
+        "connection-string-password",
+        r"(?i)(?:^|[;\s])(?:password|pwd)\s*=\s*([^;\s]+)",
+        1,
+    ),
+];
+
+/// Shannon entropy of a text, in bits per character
+pub fn shannon_entropy(text: &str) -> f64 {
+    let mut counts: HashMap<char, usize> = HashMap::new();
+    for c in text.chars() {
+        *counts.entry(c).or_default() += 1;
+    }
+    let length = text.chars().count() as f64;
+    counts
+        .values()
+        .map(|&count| {
+            let probability = count as f64 / length;
+            -probability * probability.log2()
+        })
+        .sum()
+}
+
+/// Part of the text an allowlist's patterns are matched against
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum RegexTarget {
+    /// The secret alone
+    #[default]
+    Secret,
+    /// The whole match of the rule, including the label around the secret
+    Match,
+    /// The line holding the match
+    Line,
+}
+
+/// Secrets that look like a rule match but must not be masked
+#[derive(Debug, Clone, Default)]
+pub struct Allowlist {
+    /// Patterns matched against the secret, or the part of the text given by `regex_target`
+    pub regexes: Vec<Regex>,
+    pub regex_target: RegexTarget,
+    /// Words that, when contained in the secret, mark it as a placeholder
+    pub stopwords: Vec<String>,
+    /// Whether both the regexes and the stopwords must match, instead of either
+    pub match_all: bool,
+}
+
+impl Allowlist {
+    fn allows(&self, text: &str, matched: Range<usize>, secret: &str) -> bool {
+        let target = match self.regex_target {
+            RegexTarget::Secret => secret,
+            RegexTarget::Match => &text[matched],
+            RegexTarget::Line => {
+                let start = text[..matched.start].rfind('\n').map_or(0, |i| i + 1);
+                let end = text[matched.end..]
+                    .find('\n')
+                    .map_or(text.len(), |i| matched.end + i);
+                &text[start..end]
+            }
+        };
+        let lowercase = secret.to_lowercase();
+        let regexes = (!self.regexes.is_empty())
+            .then(|| self.regexes.iter().any(|regex| regex.is_match(target)));
+        let stopwords = (!self.stopwords.is_empty()).then(|| {
+            self.stopwords
+                .iter()
+                .any(|stopword| lowercase.contains(stopword.as_str()))
+        });
+        let mut checks = regexes.into_iter().chain(stopwords);
+        if self.match_all {
+            checks.all(|allowed| allowed)
+        } else {
+            checks.any(|allowed| allowed)
+        }
+    }
+}
+
+/// A named pattern recognizing one kind of secret, such as a provider token
+#[derive(Debug, Clone)]
+pub struct SecretRule {
+    pub id: String,
+    pub regex: Regex,
+    /// Capture group holding the secret, 0 for the first group or the whole match
+    pub secret_group: usize,
+    /// Minimum Shannon entropy of the secret
+    pub entropy: Option<f64>,
+    /// Lowercase words of which one must appear in the text for the rule to run
+    pub keywords: Vec<String>,
+    /// A match is skipped when any of the allowlists allows it
+    pub allowlists: Vec<Allowlist>,
+}
+
+impl SecretRule {
+    /// Compile a rule matching the whole secret
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Config` - If the pattern is not a valid regular expression
+    ///
+    pub fn new(id: impl Into<String>, pattern: &str) -> Result<Self> {
+        let id = id.into();
+        let regex = Regex::new(pattern).map_err(|e| {
+            Error::Config(format!("Invalid pattern for secret rule '{}': {}", id, e))
+        })?;
+        Ok(Self {
+            id,
+            regex,
+            secret_group: 0,
+            entropy: None,
+            keywords: Vec::new(),
+            allowlists: Vec::new(),
+        })
+    }
+
+    /// Only mask the given capture group of each match
+    pub fn with_secret_group(mut self, group: usize) -> Self {
+        self.secret_group = group;
+        self
+    }
+
+    /// Whole matches and secrets in a text, before any allowlist
+    fn secrets<'t>(&self, text: &'t str) -> impl Iterator<Item = (Range<usize>, regex::Match<'t>)> {
+        let group = match self.secret_group {
+            0 if self.regex.captures_len() > 1 => 1,
+            group => group,
+        };
+        self.regex.captures_iter(text).filter_map(move |captures| {
+            let matched = captures.get(0)?;
+            captures
+                .get(group)
+                .or(Some(matched))
+                .filter(|secret| !secret.is_empty())
+                .map(|secret| (matched.range(), secret))
+        })
+    }
+}
+
+/// Detector of API keys, tokens and credentials using per-provider rules
+///
+/// The default detector holds built-in rules for AWS, GCP, GitHub, Slack and
+/// Stripe tokens, JWTs, PEM private key headers and connection strings.
+/// More rules can be loaded from a gitleaks configuration file.
+#[derive(Debug, Clone)]
+pub struct SecretDetector {
+    rules: Vec<SecretRule>,
+    /// Apply to every rule
+    allowlists: Vec<Allowlist>,
+}
+
+impl Default for SecretDetector {
+    fn default() -> Self {
+        // Compiled once, clones share the compiled patterns
+        static BUILTIN: OnceLock<Vec<SecretRule>> = OnceLock::new();
+        let rules = BUILTIN.get_or_init(|| {
+            BUILTIN_RULES
+                .iter()
+                .map(|(id, pattern, group)| {
+                    SecretRule::new(*id, pattern)
+                        .expect("built-in secret rule must compile")
+                        .with_secret_group(*group)
+                })
+                .collect()
+        });
+        Self {
+            rules: rules.clone(),
+            allowlists: Vec::new(),
+        }
+    }
+}
+
+impl SecretDetector {
+    /// Create a detector without any rules
+    pub fn empty() -> Self {
+        Self {
+            rules: Vec::new(),
+            allowlists: Vec::new(),
+        }
+    }
+
+    /// Add rules
+    pub fn with_rules(mut self, rules: impl IntoIterator<Item = SecretRule>) -> Self {
+        self.rules.extend(rules);
+        self
+    }
+
+    /// Add the rules and the global allowlists of another detector
+    pub fn merge(mut self, other: SecretDetector) -> Self {
+        self.rules.extend(other.rules);
+        self.allowlists.extend(other.allowlists);
+        self
+    }
+
+    pub fn rules(&self) -> &[SecretRule] {
+        &self.rules
+    }
+
+    /// Find the secrets in a text
+    ///
+    /// # Arguments
+    ///
+    /// * `text` - The text to search
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(Range<usize>, Verdict)>` - Byte range of each secret, without
+    ///   the surrounding label, and the verdict naming its rule
+    ///
+    pub fn find(&self, text: &str) -> Vec<(Range<usize>, Verdict)> {
+        let lowercase = text.to_lowercase();
+        let mut found = Vec::new();
+        for rule in &self.rules {
+            if !rule.keywords.is_empty()
+                && !rule
+                    .keywords
+                    .iter()
+                    .any(|keyword| lowercase.contains(keyword.as_str()))
+            {
+                continue;
+            }
+
+            for (matched, secret) in rule.secrets(text) {
+                let value = secret.as_str();
+                if rule
+                    .entropy
+                    .is_some_and(|minimum| shannon_entropy(value) < minimum)
+                    || rule
+                        .allowlists
+                        .iter()
+                        .chain(&self.allowlists)
+                        .any(|allowlist| allowlist.allows(text, matched.clone(), value))
+                {
+                    debug!("Secret rule '{}' match allowed", rule.id);
+                    continue;
+                }
+                let reason = format!("matched secret rule '{}'", rule.id);
+                found.push((
+                    secret.range(),
+                    Verdict::sensitive(reason, SECRET_RULES_SOURCE),
+                ));
+            }
+        }
+        found
+    }
+}
+
+/// Settings that only describe a rule and do not change what it matches
+const DESCRIPTIVE_KEYS: &[&str] = &["title", "description", "tags"];
+
+#[derive(Debug, Deserialize)]
+struct GitleaksConfig {
+    #[serde(default)]
+    rules: Vec<GitleaksRule>,
+    #[serde(default)]
+    allowlist: GitleaksAllowlist,
+    /// Newer gitleaks versions accept several global allowlists
+    #[serde(default)]
+    allowlists: Vec<GitleaksAllowlist>,
+    #[serde(flatten)]
+    other: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GitleaksRule {
+    id: String,
+    regex: Option<String>,
+    path: Option<String>,
+    #[serde(default)]
+    secret_group: usize,
+    entropy: Option<f64>,
+    #[serde(default)]
+    keywords: Vec<String>,
+    #[serde(default)]
+    allowlist: GitleaksAllowlist,
+    /// Newer gitleaks versions accept several allowlists per rule
+    #[serde(default)]
+    allowlists: Vec<GitleaksAllowlist>,
+    #[serde(flatten)]
+    other: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GitleaksAllowlist {
+    #[serde(default)]
+    regexes: Vec<String>,
+    regex_target: Option<String>,
+    #[serde(default)]
+    stopwords: Vec<String>,
+    condition: Option<String>,
+    #[serde(default)]
+    paths: Vec<String>,
+    #[serde(default)]
+    commits: Vec<String>,
+    #[serde(flatten)]
+    other: BTreeMap<String, toml::Value>,
+}
+
+impl GitleaksAllowlist {
+    /// Compile the allowlist, `None` when it can never allow a secret found in an image
+    fn compile(self, context: &str) -> Result<Option<Allowlist>> {
+        warn_unsupported(context, &self.other);
+        let regex_target = match self.regex_target.as_deref() {
+            None | Some("secret") => RegexTarget::Secret,
+            Some("match") => RegexTarget::Match,
+            Some("line") => RegexTarget::Line,
+            Some(other) => {
+                return Err(Error::Config(format!(
+                    "Invalid regexTarget '{}' in {}",
+                    other, context
+                )));
+            }
+        };
+        let match_all = match self.condition.as_deref().map(str::to_uppercase).as_deref() {
+            None | Some("OR") => false,
+            Some("AND") => true,
+            Some(_) => {
+                return Err(Error::Config(format!(
+                    "Invalid condition {:?} in {}",
+                    self.condition, context
+                )));
+            }
+        };
+
+        // Images have no file path or commit, such entries never match
+        if !self.paths.is_empty() || !self.commits.is_empty() {
+            if match_all {
+                warn!(
+                    "Ignoring {}: its paths or commits can never match an image",
+                    context
+                );
+                return Ok(None);
+            }
+            debug!("Ignoring the paths and commits of {}", context);
+        }
+        if self.regexes.is_empty() && self.stopwords.is_empty() {
+            return Ok(None);
+        }
+
+        let regexes = self
+            .regexes
+            .iter()
+            .map(|pattern| {
+                Regex::new(pattern)
+                    .map_err(|e| Error::Config(format!("Invalid allowlist regex: {}", e)))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Some(Allowlist {
+            regexes,
+            regex_target,
+            stopwords: self
+                .stopwords
+                .iter()
+                .map(|word| word.to_lowercase())
+                .collect(),
+            match_all,
+        }))
+    }
+}
+
+/// Log the settings of a gitleaks configuration that are not supported
+fn warn_unsupported(context: &str, other: &BTreeMap<String, toml::Value>) {
+    for key in other
+        .keys()
+        .filter(|key| !DESCRIPTIVE_KEYS.contains(&key.as_str()))
+    {
+        warn!("Ignoring unsupported setting '{}' of {}", key, context);
+    }
+}
+
+/// Parse a gitleaks configuration file
+///
+/// The `id`, `regex`, `secretGroup`, `entropy`, `keywords` and allowlist
+/// (`regexes`, `regexTarget`, `stopwords` and `condition`) settings of each
+/// `[[rules]]` entry are used, as well as the global allowlists. Images have
+/// no file path, so rules with a `path` are skipped, and allowlist `paths`
+/// and `commits` never match. Other unsupported settings are logged.
+///
+/// # Arguments
+///
+/// * `contents` - The TOML contents of the file
+///
+/// # Returns
+///
+/// * `Result<SecretDetector>` - A detector holding only the rules of the file
+///
+/// # Errors
+///
+/// * `Error::Config` - If the file is not valid TOML or a pattern or setting is invalid
+///
+pub fn parse_gitleaks_rules(contents: &str) -> Result<SecretDetector> {
+    let config: GitleaksConfig =
+        toml::from_str(contents).map_err(|e| Error::Config(e.to_string()))?;
+    warn_unsupported("the secret rules", &config.other);
+
+    let mut detector = SecretDetector::empty();
+    for allowlist in std::iter::once(config.allowlist).chain(config.allowlists) {
+        detector
+            .allowlists
+            .extend(allowlist.compile("the global allowlist")?);
+    }
+    for rule in config.rules {
+        let context = format!("secret rule '{}'", rule.id);
+        warn_unsupported(&context, &rule.other);
+        // A path restricts the rule to some files, applying it to images would change its meaning
+        let (Some(pattern), None) = (&rule.regex, &rule.path) else {
+            warn!("Skipping {}: file paths do not apply to images", context);
+            continue;
+        };
+        let mut compiled =
+            SecretRule::new(rule.id.as_str(), pattern)?.with_secret_group(rule.secret_group);
+        compiled.entropy = rule.entropy;
+        compiled.keywords = rule.keywords.iter().map(|k| k.to_lowercase()).collect();
+        for allowlist in std::iter::once(rule.allowlist).chain(rule.allowlists) {
+            compiled
+                .allowlists
+                .extend(allowlist.compile(&format!("the allowlist of {}", context))?);
+        }
+        detector.rules.push(compiled);
+    }
+    Ok(detector)
+}
+
+/// Read a gitleaks configuration file, see [`parse_gitleaks_rules`]
+///
+/// # Errors
+///
+/// * `Error::Io` - If the file cannot be read
+/// * `Error::Config` - If the file is invalid
+///
+pub fn load_gitleaks_rules(path: &Path) -> Result<SecretDetector> {
+    let contents = fs::read_to_string(path)
+        .map_err(|e| Error::io(format!("Failed to read secret rules {:?}", path), e))?;
+    parse_gitleaks_rules(&contents)
+        .map_err(|e| Error::Config(format!("Invalid secret rules {:?}: {}", path, e)))
+}
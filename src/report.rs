@@ -0,0 +1,155 @@
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::ops::Range;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::mask::MaskRegion;
+use crate::ocr::classifier::Verdict;
+
+/// Everything detected and masked while processing one image
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+    /// The input file, when the image was read from disk
+    pub input: Option<PathBuf>,
+    /// The output file, when the masked image was written to disk
+    pub output: Option<PathBuf>,
+    /// SHA-256 of the encoded input image, when the image was given encoded
+    pub input_sha256: Option<String>,
+    /// SHA-256 of the encoded output image, when the image was encoded
+    pub output_sha256: Option<String>,
+    /// Name of the backend that detected text
+    pub text_detector: String,
+    /// Every detected text annotation, in detection order
+    pub texts: Vec<TextFinding>,
+    /// Every detected face
+    pub faces: Vec<FaceFinding>,
+    /// Time spent in each processing stage
+    pub timings: StageTimings,
+    /// Backend failures met while processing, the findings are partial when not empty
+    pub failures: Vec<StageFailure>,
+    /// Whether the whole image was blacked out because of a failure
+    pub blacked_out: bool,
+}
+
+/// A processing stage that relies on a backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    TextDetection,
+    FaceDetection,
+    Classification,
+}
+
+/// A backend failure recorded in a report
+#[derive(Debug, Clone, Serialize)]
+pub struct StageFailure {
+    pub stage: Stage,
+    pub error: String,
+}
+
+/// A detected text annotation and what was done with it
+#[derive(Debug, Clone, Serialize)]
+pub struct TextFinding {
+    /// The detected text
+    pub text: String,
+    /// Bounding box of the annotation, clamped to the image
+    pub bounds: Option<MaskRegion>,
+    /// Classification verdict, `None` if the annotation was not classified
+    /// (the leading whole-image annotation)
+    pub verdict: Option<Verdict>,
+    /// The region that was masked, `None` if the annotation was left visible.
+    /// Encloses every entry of `masked_parts` when only parts were masked
+    pub mask: Option<MaskRegion>,
+    /// The parts of the text that were masked, empty when the whole
+    /// annotation was masked
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub masked_parts: Vec<MaskedPart>,
+}
+
+/// A sensitive part of a text annotation and the region that hid it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MaskedPart {
+    /// Byte range of the part in the annotation text
+    pub range: Range<usize>,
+    pub region: MaskRegion,
+}
+
+/// A detected face and what was done with it
+#[derive(Debug, Clone, Serialize)]
+pub struct FaceFinding {
+    /// Bounding box of the face, clamped to the image
+    pub bounds: Option<MaskRegion>,
+    /// Detection confidence (0.0 to 1.0) reported by the detector
+    pub confidence: Option<f32>,
+    /// Name of the backend that detected the face
+    pub detector: String,
+    /// The region that was masked, `None` if the face was left visible
+    pub mask: Option<MaskRegion>,
+}
+
+/// Wall-clock time spent in each processing stage, in milliseconds
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct StageTimings {
+    pub decode_ms: f64,
+    /// Includes face detection when both are done with one Vision API request
+    pub text_detection_ms: f64,
+    pub face_detection_ms: f64,
+    pub classification_ms: f64,
+    pub masking_ms: f64,
+    pub encode_ms: f64,
+}
+
+impl StageTimings {
+    /// Convert a duration to the fractional milliseconds used in reports
+    pub fn millis(duration: Duration) -> f64 {
+        duration.as_secs_f64() * 1000.0
+    }
+
+    /// Total time across all stages
+    pub fn total_ms(&self) -> f64 {
+        self.decode_ms
+            + self.text_detection_ms
+            + self.face_detection_ms
+            + self.classification_ms
+            + self.masking_ms
+            + self.encode_ms
+    }
+}
+
+impl Report {
+    /// Regions masked because they contained sensitive text
+    pub fn masked_text_regions(&self) -> impl Iterator<Item = &MaskRegion> {
+        self.texts.iter().flat_map(|text| {
+            let whole = text.mask.as_ref().filter(|_| text.masked_parts.is_empty());
+            whole
+                .into_iter()
+                .chain(text.masked_parts.iter().map(|part| &part.region))
+        })
+    }
+
+    /// Regions masked because they contained a face
+    pub fn masked_face_regions(&self) -> impl Iterator<Item = &MaskRegion> {
+        self.faces.iter().filter_map(|face| face.mask.as_ref())
+    }
+
+    /// Whether every backend succeeded, so that the findings are complete
+    pub fn is_complete(&self) -> bool {
+        self.failures.is_empty()
+    }
+
+    /// Serialize the report as pretty-printed JSON
+    ///
+    /// # Errors
+    ///
+    /// * `serde_json::Error` - If serialization fails
+    ///
+    pub fn to_json(&self) -> serde_json::Result<String> {
+        serde_json::to_string_pretty(self)
+    }
+}
+
+/// Hex encoded SHA-256 digest of some bytes
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
@@ -0,0 +1,332 @@
+use base64::{Engine as _, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use std::env;
+use tracing::{debug, error};
+
+use crate::client::{API_KEY_HEADER, ApiClient};
+use crate::error::{Error, Result};
+use crate::face::detection::FaceAnnotation;
+use crate::ocr::detection::TextAnnotation;
+use crate::ocr::document::{FullTextAnnotation, SymbolBox, TextGranularity};
+
+/// Name of the Google Cloud Vision API in error messages
+pub(crate) const VISION_API: &str = "Google Cloud Vision API";
+
+/// Maximum number of images in one Vision `images:annotate` request
+pub const MAX_BATCH_SIZE: usize = 16;
+
+/// A Vision API feature requested for an image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum VisionFeature {
+    TextDetection,
+    /// Text detection optimized for dense text, with the page, block,
+    /// paragraph, word and symbol hierarchy in `fullTextAnnotation`
+    DocumentTextDetection,
+    FaceDetection,
+}
+
+#[derive(Debug, Serialize)]
+struct AnnotateRequest {
+    requests: Vec<Request>,
+}
+
+#[derive(Debug, Serialize)]
+struct Request {
+    image: Image,
+    features: Vec<Feature>,
+}
+
+#[derive(Debug, Serialize)]
+struct Image {
+    content: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Feature {
+    #[serde(rename = "type")]
+    feature_type: VisionFeature,
+    max_results: i32,
+}
+
+#[derive(Debug, Deserialize)]
+struct AnnotateResponse {
+    responses: Vec<AnnotateImageResponse>,
+}
+
+/// Error reported by the Vision API for a single image
+///
+/// The Vision API answers with HTTP 200 even when an image cannot be
+/// processed (unreadable data, image too large, missing permission). The
+/// error is then only reported in the response of that image.
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct VisionStatus {
+    /// gRPC status code, e.g. 3 for `INVALID_ARGUMENT` or 7 for `PERMISSION_DENIED`
+    #[serde(default)]
+    pub code: i32,
+    #[serde(default)]
+    pub message: String,
+}
+
+/// Annotations found in one image, for every requested feature
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct AnnotateImageResponse {
+    /// Set when the Vision API could not process this image
+    #[serde(default)]
+    pub error: Option<VisionStatus>,
+    #[serde(default)]
+    #[serde(rename = "textAnnotations")]
+    pub text_annotations: Vec<TextAnnotation>,
+    #[serde(default)]
+    #[serde(rename = "faceAnnotations")]
+    pub face_annotations: Vec<FaceAnnotation>,
+    /// Structured text, set by `TEXT_DETECTION` and `DOCUMENT_TEXT_DETECTION`
+    #[serde(default)]
+    #[serde(rename = "fullTextAnnotation")]
+    pub full_text_annotation: Option<FullTextAnnotation>,
+}
+
+impl AnnotateImageResponse {
+    /// Text annotations of the response
+    ///
+    /// # Arguments
+    ///
+    /// * `granularity` - The unit of the annotations built from the structured
+    ///   text, or `None` to use `textAnnotations` as returned by the API
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<TextAnnotation>` - The whole text followed by the lines, words or characters
+    ///
+    pub fn texts(&self, granularity: Option<TextGranularity>) -> Vec<TextAnnotation> {
+        match (granularity, &self.full_text_annotation) {
+            (Some(granularity), Some(document)) => document.to_annotations(granularity),
+            (Some(_), None) => Vec::new(),
+            (None, _) => self.text_annotations.clone(),
+        }
+    }
+
+    /// Text annotations of the response, with the box of each character
+    ///
+    /// Without a granularity, the words of `textAnnotations` get the
+    /// character boxes of the matching words of `fullTextAnnotation`, when
+    /// the API returned it.
+    ///
+    /// # Arguments
+    ///
+    /// * `granularity` - As for [`Self::texts`]
+    ///
+    /// # Returns
+    ///
+    /// * `Vec<(TextAnnotation, Vec<SymbolBox>)>` - The annotations returned by
+    ///   [`Self::texts`], each with the character boxes that are known
+    ///
+    pub fn texts_with_symbols(
+        &self,
+        granularity: Option<TextGranularity>,
+    ) -> Vec<(TextAnnotation, Vec<SymbolBox>)> {
+        match (granularity, &self.full_text_annotation) {
+            (Some(granularity), Some(document)) => {
+                document.to_annotations_with_symbols(granularity)
+            }
+            (Some(_), None) => Vec::new(),
+            (None, None) => self
+                .text_annotations
+                .iter()
+                .map(|annotation| (annotation.clone(), Vec::new()))
+                .collect(),
+            (None, Some(document)) => {
+                // Both lists are in reading order, words missing from either one are skipped
+                let words: Vec<_> = document.words().collect();
+                let mut next_word = 0;
+                self.text_annotations
+                    .iter()
+                    .enumerate()
+                    .map(|(index, annotation)| {
+                        let found = (index > 0)
+                            .then(|| {
+                                words[next_word..]
+                                    .iter()
+                                    .position(|word| word.text() == annotation.description)
+                            })
+                            .flatten();
+                        let symbols = match found {
+                            Some(position) => {
+                                next_word += position + 1;
+                                words[next_word - 1].symbol_boxes(0)
+                            }
+                            None => Vec::new(),
+                        };
+                        (annotation.clone(), symbols)
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// Client of the Google Cloud Vision `images:annotate` method
+///
+/// Several features can be requested at once, so that text and faces are
+/// found with a single request per image.
+#[derive(Debug, Clone)]
+pub struct VisionClient {
+    api_key: String,
+    client: ApiClient,
+}
+
+impl VisionClient {
+    /// Create a client that authenticates with the given API key
+    pub fn new(api_key: impl Into<String>) -> Self {
+        Self {
+            api_key: api_key.into(),
+            client: ApiClient::shared(),
+        }
+    }
+
+    /// Create a client using the `GCP_API_KEY` environment variable
+    ///
+    /// # Errors
+    ///
+    /// * `Error::Auth` - If `GCP_API_KEY` is not set
+    ///
+    pub fn from_env() -> Result<Self> {
+        let api_key = env::var("GCP_API_KEY").map_err(|_| Error::missing_api_key())?;
+        Ok(Self::new(api_key))
+    }
+
+    /// Send requests through the given client instead of the shared default one
+    pub fn with_client(mut self, client: ApiClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Annotate an encoded image (PNG, JPEG, ...) with the given features
+    ///
+    /// # Arguments
+    ///
+    /// * `image_data` - The encoded image bytes
+    /// * `features` - The features to detect
+    ///
+    /// # Returns
+    ///
+    /// * `Result<AnnotateImageResponse>` - The annotations of every requested feature
+    ///
+    /// # Errors
+    ///
+    /// * `Error::ImageRejected` - If the API could not process the image
+    /// * `Error` - If the request fails or the response cannot be parsed
+    ///
+    pub fn annotate(
+        &self,
+        image_data: &[u8],
+        features: &[VisionFeature],
+    ) -> Result<AnnotateImageResponse> {
+        self.annotate_batch(&[image_data], features)?
+            .pop()
+            .unwrap_or_else(|| Err(Error::malformed(VISION_API, "no responses")))
+    }
+
+    /// Annotate several encoded images with a single request
+    ///
+    /// The Vision API accepts at most [`MAX_BATCH_SIZE`] images per request.
+    ///
+    /// # Arguments
+    ///
+    /// * `images` - The encoded images
+    /// * `features` - The features to detect in every image
+    ///
+    /// # Returns
+    ///
+    /// * `Result<Vec<Result<AnnotateImageResponse>>>` - The annotations of each
+    ///   image, in the order of `images`, or the error specific to that image
+    ///   (`Error::ImageRejected` when the API reported an error for it)
+    ///
+    /// # Errors
+    ///
+    /// * `Error` - If the whole request fails or the response cannot be parsed
+    ///
+    pub fn annotate_batch(
+        &self,
+        images: &[&[u8]],
+        features: &[VisionFeature],
+    ) -> Result<Vec<Result<AnnotateImageResponse>>> {
+        let requests = images
+            .iter()
+            .map(|image_data| Request {
+                image: Image {
+                    content: general_purpose::STANDARD.encode(image_data),
+                },
+                features: features
+                    .iter()
+                    .map(|&feature_type| Feature {
+                        feature_type,
+                        max_results: 100,
+                    })
+                    .collect(),
+            })
+            .collect();
+        let request = AnnotateRequest { requests };
+
+        debug!("Sending Vision request for {} images", images.len());
+        let url = self.client.vision_url("v1/images:annotate");
+        let request = self
+            .client
+            .http()
+            .post(url)
+            .header(API_KEY_HEADER, &self.api_key)
+            .json(&request);
+        let response = self.client.send(VISION_API, request)?;
+        let response_text = response.text().map_err(|e| Error::http(VISION_API, e))?;
+
+        // OCR output is often multi-byte, cut on a character boundary
+        if let Some((end, _)) = response_text.char_indices().nth(1000) {
+            debug!(
+                "Response text (first 1000 chars): {}",
+                &response_text[..end]
+            );
+            debug!("Response text length: {}", response_text.len());
+        } else {
+            debug!("Response text: {}", &response_text);
+        }
+
+        let response_body: AnnotateResponse = serde_json::from_str(&response_text)
+            .map_err(|e| Error::malformed(VISION_API, e.to_string()))?;
+
+        if response_body.responses.len() != images.len() {
+            error!(
+                "Google Cloud Vision API returned {} responses for {} images",
+                response_body.responses.len(),
+                images.len()
+            );
+        }
+
+        // Responses are in request order, a missing or failed response fails only its image
+        let mut responses = response_body.responses.into_iter();
+        Ok((0..images.len())
+            .map(|index| {
+                let response = responses.next().ok_or_else(|| {
+                    Error::malformed(VISION_API, format!("no response for image {}", index))
+                })?;
+                if let Some(status) = response.error {
+                    error!(
+                        "Google Cloud Vision API failed to process image {}: {} (code {})",
+                        index, status.message, status.code
+                    );
+                    return Err(Error::ImageRejected {
+                        api: VISION_API.to_string(),
+                        code: status.code,
+                        message: status.message,
+                    });
+                }
+                debug!(
+                    "Image {}: {} text annotations and {} face annotations",
+                    index,
+                    response.text_annotations.len(),
+                    response.face_annotations.len()
+                );
+                Ok(response)
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,199 @@
+use anyhow::Result;
+use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
+use image_anonymizer::Error;
+use image_anonymizer::ocr::classifier::{
+    ChainClassifier, RuleBasedClassifier, SensitivityClassifier, Verdict,
+};
+use image_anonymizer::ocr::detection::{BoundingPoly, TextAnnotation, Vertex};
+use image_anonymizer::ocr::mask_list::MaskList;
+use image_anonymizer::ocr::masking::{
+    SensitiveTextCriteria, TextMask, mask_text_with_classifier, resolve_masks,
+};
+
+// Classifier that always fails, standing in for an unreachable API
+struct FailingClassifier;
+
+impl SensitivityClassifier for FailingClassifier {
+    fn name(&self) -> &str {
+        "failing"
+    }
+
+    fn classify(&self, _text: &str) -> image_anonymizer::Result<Verdict> {
+        Err(Error::Config("service unavailable".to_string()))
+    }
+
+    fn find_sensitive(
+        &self,
+        _text: &str,
+    ) -> image_anonymizer::Result<Vec<(std::ops::Range<usize>, Verdict)>> {
+        Err(Error::Config("service unavailable".to_string()))
+    }
+}
+
+// Classifier that flags text containing a fixed keyword
+struct KeywordClassifier(&'static str);
+
+impl SensitivityClassifier for KeywordClassifier {
+    fn name(&self) -> &str {
+        "keyword"
+    }
+
+    fn classify(&self, text: &str) -> image_anonymizer::Result<Verdict> {
+        Ok(Verdict {
+            sensitive: text.contains(self.0),
+            reason: format!("keyword '{}'", self.0),
+            classifier: self.name().to_string(),
+        })
+    }
+}
+
+#[test]
+fn test_rule_based_classifier() -> Result<()> {
+    let classifier = RuleBasedClassifier::default();
+
+    let verdict = classifier.classify("john.doe@example.com")?;
+    assert!(verdict.sensitive);
+    assert_eq!(verdict.classifier, "rules");
+
+    assert!(classifier.classify("+1 555 123 4567")?.sensitive);
+    assert!(!classifier.classify("Email")?.sensitive);
+    assert!(!classifier.classify("Submit")?.sensitive);
+
+    let custom = RuleBasedClassifier::empty().with_rule("ticket", r"TICKET-\d+")?;
+    assert!(custom.classify("TICKET-42")?.sensitive);
+    assert!(!custom.classify("john.doe@example.com")?.sensitive);
+
+    Ok(())
+}
+
+#[test]
+fn test_chain_classifier_falls_back() -> Result<()> {
+    let chain = ChainClassifier::new(vec![
+        Box::new(FailingClassifier),
+        Box::new(RuleBasedClassifier::default()),
+    ]);
+
+    let verdict = chain.classify("john.doe@example.com")?;
+    assert!(verdict.sensitive);
+    assert_eq!(verdict.classifier, "rules");
+
+    let all_failing = ChainClassifier::new(vec![Box::new(FailingClassifier)]);
+    assert!(all_failing.classify("anything").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_mask_text_with_custom_classifier() -> Result<()> {
+    let mut img = DynamicImage::new_rgba8(100, 100);
+    for y in 0..100 {
+        for x in 0..100 {
+            img.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+        }
+    }
+
+    let annotation = |description: &str, y: i32| TextAnnotation {
+        description: description.to_string(),
+        bounding_poly: Some(BoundingPoly {
+            vertices: vec![
+                Vertex { x: 10, y },
+                Vertex { x: 40, y },
+                Vertex { x: 40, y: y + 10 },
+                Vertex { x: 10, y: y + 10 },
+            ],
+        }),
+    };
+    let annotations = vec![
+        annotation("whole text", 0),
+        annotation("classified", 10),
+        annotation("public", 50),
+    ];
+
+    mask_text_with_classifier(
+        &mut img,
+        &annotations,
+        &[],
+        &KeywordClassifier("classified"),
+    )?;
+
+    assert_eq!(img.get_pixel(20, 15), Rgba([0, 0, 0, 128]));
+    assert_eq!(img.get_pixel(20, 55), Rgba([255, 255, 255, 255]));
+
+    Ok(())
+}
+
+// Words of one line, side by side, after the whole-text annotation
+fn line(words: &[&str]) -> Vec<TextAnnotation> {
+    let mut annotations = vec![TextAnnotation {
+        description: words.join(" "),
+        bounding_poly: None,
+    }];
+    let mut x = 10;
+    for word in words {
+        let width = word.len() as i32 * 10;
+        annotations.push(TextAnnotation {
+            description: word.to_string(),
+            bounding_poly: Some(BoundingPoly {
+                vertices: vec![
+                    Vertex { x, y: 10 },
+                    Vertex {
+                        x: x + width,
+                        y: 10,
+                    },
+                    Vertex {
+                        x: x + width,
+                        y: 30,
+                    },
+                    Vertex { x, y: 30 },
+                ],
+            }),
+        });
+        x += width + 5;
+    }
+    annotations
+}
+
+#[test]
+fn test_chain_fallback_does_not_override_primary() -> Result<()> {
+    let annotations = line(&["Updated", "2024-01-15", "10:30"]);
+    let criteria = SensitiveTextCriteria::default();
+
+    // The primary classifier finds nothing sensitive, the rules must not run
+    let chain = ChainClassifier::new(vec![
+        Box::new(KeywordClassifier("classified")),
+        Box::new(RuleBasedClassifier::default()),
+    ]);
+    assert!(chain.find_sensitive("Updated 2024-01-15 10:30")?.is_empty());
+    let mut verdicts: Vec<_> = annotations
+        .iter()
+        .map(|annotation| chain.classify(&annotation.description).ok())
+        .collect();
+    let masks = resolve_masks(
+        &annotations,
+        &mut verdicts,
+        &MaskList::default(),
+        &criteria,
+        &chain,
+    );
+    assert!(masks.iter().all(|mask| *mask == TextMask::Keep));
+
+    // Once the primary fails, the rules search the line, each part reported once
+    let annotations = line(&["Call", "+1", "555", "123", "4567"]);
+    let chain = ChainClassifier::new(vec![
+        Box::new(FailingClassifier),
+        Box::new(RuleBasedClassifier::default()),
+    ]);
+    assert!(!chain.find_sensitive("Call +1 555 123 4567")?.is_empty());
+    let mut verdicts = vec![None; annotations.len()];
+    let masks = resolve_masks(
+        &annotations,
+        &mut verdicts,
+        &MaskList::default(),
+        &criteria,
+        &chain,
+    );
+    assert_eq!(masks[1], TextMask::Keep);
+    assert_eq!(masks[3], TextMask::Parts(std::iter::once(0..3).collect()));
+
+    Ok(())
+}
@@ -1,5 +1,21 @@
+use image::{DynamicImage, GenericImageView, ImageOutputFormat, Rgba};
+use image_anonymizer::audit::AuditRecord;
+use image_anonymizer::error::parse_retry_after;
+use image_anonymizer::mask::MaskStyle;
+use image_anonymizer::ocr::classifier::RuleBasedClassifier;
+use image_anonymizer::ocr::detection::VisionTextDetector;
+use image_anonymizer::ocr::document::TextGranularity;
+use image_anonymizer::{Anonymizer, ApiClient, ClientConfig, Error, FailurePolicy, RetryPolicy};
+use serde_json::json;
 use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::net::TcpListener;
 use std::path::Path;
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+const MOCK_RESPONSE: &str = r#"{"responses": [{"textAnnotations": [{"description": "mock"}]}]}"#;
 
 // Note: Since detect_text_with_api makes external API calls,
 // we'll focus on testing the response parsing logic with mocked data.
@@ -82,3 +98,435 @@ fn test_api_key_environment_variable() {
         }
     }
 }
+
+// Serve one HTTP request per canned (status line, JSON body) response, returning
+// the base URL of the server and a handle yielding the received request heads
+// (request line and headers)
+fn serve(
+    responses: Vec<(&'static str, &'static str)>,
+) -> (String, thread::JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("Failed to bind mock server");
+    let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+    let handle = thread::spawn(move || {
+        let mut request_heads = Vec::new();
+        for (status, body) in responses {
+            let (stream, _) = listener.accept().expect("Failed to accept connection");
+            let mut reader = BufReader::new(stream);
+
+            let mut request_head = String::new();
+            reader.read_line(&mut request_head).unwrap();
+
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                if header.trim().is_empty() {
+                    break;
+                }
+                request_head.push_str(&header);
+                if let Some(value) = header.to_lowercase().strip_prefix("content-length:") {
+                    content_length = value.trim().parse().unwrap();
+                }
+            }
+            let mut request_body = vec![0; content_length];
+            reader.read_exact(&mut request_body).unwrap();
+
+            let response = format!(
+                "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+            request_heads.push(request_head);
+        }
+        request_heads
+    });
+
+    (base_url, handle)
+}
+
+// Client for the mock server, retrying without delay
+fn mock_client(base_url: &str, max_attempts: u32) -> ApiClient {
+    ApiClient::new(ClientConfig {
+        vision_base_url: format!("{}/", base_url),
+        retry: RetryPolicy {
+            max_attempts,
+            initial_backoff: Duration::ZERO,
+            ..RetryPolicy::default()
+        },
+        ..ClientConfig::default()
+    })
+    .expect("Failed to build client")
+}
+
+#[test]
+fn test_vision_base_url_override() {
+    let (base_url, server) = serve(vec![("200 OK", MOCK_RESPONSE)]);
+    let detector = VisionTextDetector::new("test-key").with_client(mock_client(&base_url, 1));
+
+    let annotations = detector
+        .detect_bytes(b"not really an image")
+        .expect("Mock request failed");
+    assert_eq!(annotations.len(), 1);
+    assert_eq!(annotations[0].description, "mock");
+
+    let request_heads = server.join().unwrap();
+    assert!(request_heads[0].starts_with("POST /v1/images:annotate "));
+    assert!(request_heads[0].contains("x-goog-api-key: test-key\r\n"));
+}
+
+#[test]
+fn test_transient_errors_are_retried() {
+    let (base_url, server) = serve(vec![
+        ("503 Service Unavailable", "{}"),
+        ("429 Too Many Requests", "{}"),
+        ("200 OK", MOCK_RESPONSE),
+    ]);
+    let detector = VisionTextDetector::new("test-key").with_client(mock_client(&base_url, 3));
+
+    let annotations = detector
+        .detect_bytes(b"not really an image")
+        .expect("Request should succeed after retries");
+    assert_eq!(annotations[0].description, "mock");
+    assert_eq!(server.join().unwrap().len(), 3);
+
+    // Bad credentials are reported at once
+    let (base_url, server) = serve(vec![("403 Forbidden", "{}")]);
+    let detector = VisionTextDetector::new("test-key").with_client(mock_client(&base_url, 3));
+    let result = detector.detect_bytes(b"not really an image");
+    assert!(matches!(result, Err(Error::Auth(_))));
+    assert_eq!(server.join().unwrap().len(), 1);
+}
+
+#[test]
+fn test_retry_delay() {
+    let policy = RetryPolicy {
+        jitter: 0.0,
+        ..RetryPolicy::default()
+    };
+    let error = Error::ApiError {
+        api: "test".to_string(),
+        status: 503,
+        body: String::new(),
+        retry_after: None,
+    };
+    assert_eq!(policy.delay(1, &error), Duration::from_millis(500));
+    assert_eq!(policy.delay(3, &error), Duration::from_secs(2));
+    assert_eq!(policy.delay(20, &error), policy.max_backoff);
+
+    let rate_limited = Error::RateLimited {
+        api: "test".to_string(),
+        retry_after: Some(Duration::from_secs(7)),
+    };
+    assert_eq!(policy.delay(1, &rate_limited), Duration::from_secs(7));
+
+    // Both forms of the header, a date in the past meaning no delay
+    let now = UNIX_EPOCH + Duration::from_secs(784_111_777);
+    assert_eq!(
+        parse_retry_after(" 12 ", now),
+        Some(Duration::from_secs(12))
+    );
+    assert_eq!(
+        parse_retry_after("Sun, 06 Nov 1994 08:49:57 GMT", now),
+        Some(Duration::from_secs(20))
+    );
+    assert_eq!(
+        parse_retry_after("Sun, 06 Nov 1994 08:49:00 GMT", now),
+        Some(Duration::ZERO)
+    );
+    assert_eq!(parse_retry_after("soon", now), None);
+}
+
+#[test]
+fn test_retry_after_is_honored_for_unavailable() {
+    // The header is appended to the status line of the canned response
+    let (base_url, server) = serve(vec![
+        ("503 Service Unavailable\r\nRetry-After: 1", "{}"),
+        ("200 OK", MOCK_RESPONSE),
+    ]);
+    let detector = VisionTextDetector::new("test-key").with_client(mock_client(&base_url, 2));
+
+    let start = Instant::now();
+    detector
+        .detect_bytes(b"not really an image")
+        .expect("Request should succeed after a retry");
+    assert!(start.elapsed() >= Duration::from_secs(1));
+    assert_eq!(server.join().unwrap().len(), 2);
+}
+
+#[test]
+fn test_text_and_faces_in_one_request() {
+    let (base_url, server) = serve(vec![(
+        "200 OK",
+        r#"{"responses": [{
+            "textAnnotations": [{"description": "hello"}],
+            "faceAnnotations": [{
+                "boundingPoly": {"vertices": [{"x": 2, "y": 2}, {"x": 8, "y": 8}]},
+                "detectionConfidence": 0.9
+            }]
+        }]}"#,
+    )]);
+    let anonymizer = Anonymizer::builder()
+        .api_key("test-key")
+        .client(mock_client(&base_url, 1))
+        .classifier(RuleBasedClassifier::default())
+        .mask_faces(true)
+        .build()
+        .expect("Failed to build anonymizer");
+
+    let mut png = Vec::new();
+    DynamicImage::new_rgba8(10, 10)
+        .write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)
+        .unwrap();
+    let (_, report) = anonymizer
+        .anonymize_bytes_with_report(&png)
+        .expect("Mock request failed");
+
+    assert_eq!(report.texts.len(), 1);
+    assert_eq!(report.faces.len(), 1);
+    assert_eq!(report.masked_face_regions().count(), 1);
+    assert_eq!(server.join().unwrap().len(), 1);
+}
+
+#[test]
+fn test_directory_is_sent_in_batches() {
+    let input_dir = Path::new("./test_output_batch/input");
+    let output_dir = Path::new("./test_output_batch/output");
+    fs::create_dir_all(input_dir).unwrap();
+    for name in ["a.png", "c.png", "d.png"] {
+        DynamicImage::new_rgba8(10, 10)
+            .save(input_dir.join(name))
+            .unwrap();
+    }
+    fs::write(input_dir.join("b.png"), b"not an image").unwrap();
+    fs::write(input_dir.join("notes.txt"), b"ignored").unwrap();
+
+    // a and c share the first request, d is sent alone
+    let (base_url, server) = serve(vec![
+        (
+            "200 OK",
+            r#"{"responses": [{"textAnnotations": [{"description": "a"}]}, {}]}"#,
+        ),
+        ("200 OK", MOCK_RESPONSE),
+    ]);
+    let anonymizer = Anonymizer::builder()
+        .api_key("test-key")
+        .client(mock_client(&base_url, 1))
+        .classifier(RuleBasedClassifier::default())
+        .batch_size(3)
+        .build()
+        .expect("Failed to build anonymizer");
+
+    let results = anonymizer
+        .process_dir(input_dir, output_dir)
+        .expect("Failed to read input directory");
+    fs::remove_dir_all("./test_output_batch").unwrap();
+
+    let names: Vec<_> = results
+        .iter()
+        .map(|(path, _)| path.file_name().unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(names, ["a.png", "b.png", "c.png", "d.png"]);
+
+    assert_eq!(results[0].1.as_ref().unwrap().texts[0].text, "a");
+    assert!(matches!(results[1].1, Err(Error::Decode(_))));
+    assert!(results[2].1.as_ref().unwrap().texts.is_empty());
+    assert_eq!(results[3].1.as_ref().unwrap().texts[0].text, "mock");
+    assert_eq!(server.join().unwrap().len(), 2);
+}
+
+#[test]
+fn test_image_error_fails_the_image() {
+    let (base_url, server) = serve(vec![(
+        "200 OK",
+        r#"{"responses": [{"error": {"code": 3, "message": "Bad image data."}}]}"#,
+    )]);
+    let anonymizer = Anonymizer::builder()
+        .api_key("test-key")
+        .client(mock_client(&base_url, 1))
+        .classifier(RuleBasedClassifier::default())
+        .build()
+        .expect("Failed to build anonymizer");
+
+    let input_dir = Path::new("./test_output_image_error");
+    fs::create_dir_all(input_dir).unwrap();
+    let input_path = input_dir.join("image.png");
+    DynamicImage::new_rgba8(10, 10).save(&input_path).unwrap();
+
+    let result = anonymizer.process_file(&input_path, input_dir);
+    let output_written = input_dir.join("masked_image.png").exists();
+    fs::remove_dir_all(input_dir).unwrap();
+
+    match result {
+        Err(Error::ImageRejected { code, message, .. }) => {
+            assert_eq!(code, 3);
+            assert_eq!(message, "Bad image data.");
+        }
+        other => panic!("Expected a rejected image, got {:?}", other.map(|_| ())),
+    }
+    assert!(
+        !output_written,
+        "No output should be saved for a rejected image"
+    );
+    server.join().unwrap();
+}
+
+#[test]
+fn test_invalid_client_config() {
+    let result = ApiClient::new(ClientConfig {
+        proxy: Some("not a url".to_string()),
+        ..ClientConfig::default()
+    });
+    assert!(matches!(result, Err(Error::Config(_))));
+
+    let result = ApiClient::new(ClientConfig {
+        ca_certs: vec!["nonexistent.pem".into()],
+        ..ClientConfig::default()
+    });
+    assert!(matches!(result, Err(Error::Io { .. })));
+}
+
+#[test]
+fn test_document_text_granularity() {
+    let response = r#"{"responses": [{"fullTextAnnotation": {
+        "text": "call 555-0100\nok\n",
+        "pages": [{"blocks": [{
+            "boundingBox": {"vertices": [{"x": 0, "y": 0}, {"x": 60, "y": 0}, {"x": 60, "y": 30}, {"x": 0, "y": 30}]},
+            "paragraphs": [{"words": [
+                {"boundingBox": {"vertices": [{"x": 0, "y": 0}, {"x": 20, "y": 0}, {"x": 20, "y": 10}, {"x": 0, "y": 10}]},
+                 "symbols": [{"text": "c"}, {"text": "a"}, {"text": "l"},
+                             {"text": "l", "property": {"detectedBreak": {"type": "SPACE"}}}]},
+                {"boundingBox": {"vertices": [{"x": 25, "y": 0}, {"x": 60, "y": 0}, {"x": 60, "y": 12}, {"x": 25, "y": 12}]},
+                 "symbols": [{"text": "555-010"},
+                             {"text": "0", "property": {"detectedBreak": {"type": "EOL_SURE_SPACE"}}}]},
+                {"boundingBox": {"vertices": [{"x": 0, "y": 20}, {"x": 10, "y": 20}, {"x": 10, "y": 30}, {"x": 0, "y": 30}]},
+                 "symbols": [{"text": "o", "confidence": 0.9},
+                             {"text": "k", "property": {"detectedBreak": {"type": "LINE_BREAK"}}}]}
+            ]}]
+        }]}]
+    }}]}"#;
+    let (base_url, server) = serve(vec![("200 OK", response)]);
+
+    let lines = VisionTextDetector::new("test-key")
+        .with_client(mock_client(&base_url, 1))
+        .document(TextGranularity::Line)
+        .detect_bytes(b"not really an image")
+        .expect("Mock request failed");
+    let texts: Vec<_> = lines.iter().map(|a| a.description.as_str()).collect();
+    assert_eq!(texts, ["call 555-0100\nok\n", "call 555-0100", "ok"]);
+    let vertices = &lines[1].bounding_poly.as_ref().unwrap().vertices;
+    assert_eq!((vertices[0].x, vertices[0].y), (0, 0));
+    assert_eq!((vertices[2].x, vertices[2].y), (60, 12));
+    assert_eq!(server.join().unwrap().len(), 1);
+}
+
+// Word of a document text response, with a 10 pixel wide box per character
+fn document_word(text: &str, x: i32, confidence: f32, detected_break: &str) -> serde_json::Value {
+    let bounding_box = |min_x: i32, max_x: i32| {
+        json!({"vertices": [{"x": min_x, "y": 0}, {"x": max_x, "y": 0},
+                            {"x": max_x, "y": 10}, {"x": min_x, "y": 10}]})
+    };
+    let count = text.chars().count();
+    let symbols: Vec<_> = text
+        .chars()
+        .enumerate()
+        .map(|(index, c)| {
+            let min_x = x + 10 * index as i32;
+            let mut symbol =
+                json!({"text": c.to_string(), "boundingBox": bounding_box(min_x, min_x + 8)});
+            if index + 1 == count {
+                symbol["property"] = json!({"detectedBreak": {"type": detected_break}});
+            }
+            symbol
+        })
+        .collect();
+    json!({
+        "boundingBox": bounding_box(x, x + 10 * count as i32),
+        "confidence": confidence,
+        "symbols": symbols,
+    })
+}
+
+#[test]
+fn test_character_granularity_masks_each_character() {
+    let words = [
+        document_word("mail:", 0, 0.99, "SPACE"),
+        document_word("zz", 60, 0.2, "SPACE"),
+        document_word("jo@x.io", 90, 0.98, "LINE_BREAK"),
+    ];
+    let response = json!({"responses": [{"fullTextAnnotation": {
+        "text": "mail: zz jo@x.io\n",
+        "pages": [{"blocks": [{"paragraphs": [{"words": words}]}]}],
+    }}]});
+    // The mock server serves static responses
+    let response: &'static str = Box::leak(response.to_string().into_boxed_str());
+    let (base_url, server) = serve(vec![("200 OK", response)]);
+    let anonymizer = Anonymizer::builder()
+        .api_key("test-key")
+        .client(mock_client(&base_url, 1))
+        .classifier(RuleBasedClassifier::default())
+        .text_mask_style(MaskStyle::Solid([0, 0, 0, 255]))
+        .document_text(TextGranularity::Character)
+        .min_text_confidence(0.5)
+        .build()
+        .expect("Failed to build anonymizer");
+
+    let mut img = DynamicImage::new_rgba8(200, 20);
+    let report = anonymizer.mask(&mut img).expect("Mock request failed");
+    server.join().unwrap();
+
+    // Text is classified by word, the unsure word is ignored
+    let texts: Vec<_> = report.texts.iter().map(|t| t.text.as_str()).collect();
+    assert_eq!(texts[1..], ["mail:", "jo@x.io"]);
+    assert!(report.texts[1].mask.is_none());
+    assert!(report.texts[2].mask.is_some());
+
+    // The address is covered character by character, leaving the gaps visible
+    let parts = &report.texts[2].masked_parts;
+    assert_eq!(parts.len(), 7);
+    assert_eq!((parts[0].region.min_x, parts[0].region.max_x), (90, 98));
+    assert_eq!(parts[6].range, 6..7);
+    assert_eq!(img.get_pixel(94, 5), Rgba([0, 0, 0, 255]));
+    assert_eq!(img.get_pixel(99, 5), Rgba([0, 0, 0, 0]));
+    assert_eq!(img.get_pixel(154, 5), Rgba([0, 0, 0, 255]));
+}
+
+#[test]
+fn test_api_key_is_not_leaked_by_failures() {
+    let (base_url, server) = serve(vec![(
+        "200 OK",
+        r#"{"responses": [{"textAnnotations": [{"description": "hello"}]}]}"#,
+    )]);
+    // Nothing listens on the Gemini port once the listener is dropped
+    let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+    let gemini_base_url = format!("http://{}", closed.local_addr().unwrap());
+    drop(closed);
+
+    let client = ApiClient::new(ClientConfig {
+        vision_base_url: base_url,
+        gemini_base_url,
+        retry: RetryPolicy::none(),
+        ..ClientConfig::default()
+    })
+    .expect("Failed to build client");
+    let anonymizer = Anonymizer::builder()
+        .api_key("leaky-test-key")
+        .client(client)
+        .on_failure(FailurePolicy::Blackout)
+        .build()
+        .expect("Failed to build anonymizer");
+
+    let mut img = DynamicImage::new_rgba8(10, 10);
+    let report = anonymizer
+        .mask(&mut img)
+        .expect("Failures should be recorded");
+    server.join().unwrap();
+
+    assert_eq!(report.failures.len(), 1);
+    assert!(!report.failures[0].error.contains("leaky-test-key"));
+    let audit = serde_json::to_string(&AuditRecord::from_report(&report, false)).unwrap();
+    assert!(audit.contains("Failed to reach"));
+    assert!(!audit.contains("leaky-test-key"));
+}
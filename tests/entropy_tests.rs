@@ -0,0 +1,33 @@
+use image_anonymizer::ocr::entropy::{EntropyThresholds, find_high_entropy, score_token};
+
+#[test]
+fn test_find_random_tokens() {
+    let thresholds = EntropyThresholds::default();
+    let text = "token=\"q8Zr2mXv7LpT4kW9bNc3\" for UserRepositoryImplementation";
+    let found = find_high_entropy(text, &thresholds);
+    assert_eq!(found.len(), 1);
+    assert_eq!(&text[found[0].0.clone()], "q8Zr2mXv7LpT4kW9bNc3");
+    assert_eq!(found[0].1.classifier, "entropy");
+
+    let strict = EntropyThresholds {
+        min_length: 24,
+        ..thresholds
+    };
+    assert!(find_high_entropy(text, &strict).is_empty());
+}
+
+#[test]
+fn test_ocr_confusions_do_not_add_digits() {
+    assert_eq!(score_token("C0NFIGURATI0N").classes, 1);
+    assert_eq!(score_token("passw0rd").classes, 1);
+    assert_eq!(score_token("passw0rd"), score_token("password"));
+    assert_eq!(score_token("aB3dEf7").classes, 3);
+
+    // Uppercase and symbols only once the zeros are read as letters
+    let thresholds = EntropyThresholds::default();
+    assert!(find_high_entropy("DEPL0YMENT_C0NFIGURATI0N", &thresholds).is_empty());
+    assert_eq!(
+        find_high_entropy("DEPL4YMENT_C9NFIGURATI7N", &thresholds).len(),
+        1
+    );
+}
@@ -1,3 +1,6 @@
+use image_anonymizer::ocr::classifier::SensitivityClassifier;
+use image_anonymizer::ocr::gemini::GeminiClassifier;
+use image_anonymizer::{ApiClient, ClientConfig, Error, RetryPolicy};
 use std::env;
 
 // Note: These tests focus on the sensitivity detection logic and
@@ -81,3 +84,27 @@ fn test_fallback_sensitivity_logic() {
         env::set_var("GEMINI_MODEL", "gemini-2.0-flash-lite");
     }
 }
+
+#[test]
+fn test_api_key_is_not_in_errors() {
+    // Nothing listens on a port that was just released
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("Failed to reserve a port")
+        .port();
+    let client = ApiClient::new(ClientConfig {
+        gemini_base_url: format!("http://127.0.0.1:{}", port),
+        retry: RetryPolicy {
+            max_attempts: 1,
+            ..RetryPolicy::default()
+        },
+        ..ClientConfig::default()
+    })
+    .expect("Failed to build client");
+    let classifier =
+        GeminiClassifier::new("leaky-test-key", "gemini-2.0-flash-lite").with_client(client);
+
+    let error = classifier.classify("john.doe@example.com").unwrap_err();
+    assert!(matches!(error, Error::Http { .. }));
+    assert!(!error.to_string().contains("leaky-test-key"));
+}
@@ -1,7 +1,18 @@
 use anyhow::Result;
-use image::{DynamicImage, GenericImage, Rgba};
+use image::{DynamicImage, GenericImage, GenericImageView, ImageFormat, ImageOutputFormat, Rgba};
+use image_anonymizer::audit::{AuditRecord, MaskCategory};
+use image_anonymizer::face::detection::{
+    BoundingPoly as FaceBoundingPoly, FaceAnnotation, FaceDetector, Vertex as FaceVertex,
+};
+use image_anonymizer::mask::MaskStyle;
+use image_anonymizer::ocr::classifier::RuleBasedClassifier;
+use image_anonymizer::ocr::detection::{BoundingPoly, TextAnnotation, TextDetector, Vertex};
+use image_anonymizer::ocr::mask_list::MaskPattern;
+use image_anonymizer::report::Stage;
+use image_anonymizer::{Error, FailurePolicy};
 use std::env;
 use std::fs;
+use std::io::Cursor;
 use std::path::{Path, PathBuf};
 
 // Helper function to create a test image with sample text
@@ -94,3 +105,458 @@ fn test_process_image_nonexistent_input() -> Result<()> {
 
     Ok(())
 }
+
+// Detector returning fixed annotations so the pipeline can run without network access
+struct StaticTextDetector {
+    annotations: Vec<TextAnnotation>,
+}
+
+impl TextDetector for StaticTextDetector {
+    fn detect_text(&self, _image: &DynamicImage) -> image_anonymizer::Result<Vec<TextAnnotation>> {
+        Ok(self.annotations.clone())
+    }
+}
+
+fn annotation(description: &str, min: (i32, i32), max: (i32, i32)) -> TextAnnotation {
+    TextAnnotation {
+        description: description.to_string(),
+        bounding_poly: Some(BoundingPoly {
+            vertices: vec![
+                Vertex { x: min.0, y: min.1 },
+                Vertex { x: max.0, y: min.1 },
+                Vertex { x: max.0, y: max.1 },
+                Vertex { x: min.0, y: max.1 },
+            ],
+        }),
+    }
+}
+
+#[test]
+fn test_process_image_with_custom_text_detector() -> Result<()> {
+    let test_dir = PathBuf::from("./test_output_custom_detector");
+    let image_path = test_dir.join("test_image.png");
+    create_test_image(&image_path)?;
+    let output_dir = test_dir.join("output");
+
+    let detector = StaticTextDetector {
+        annotations: vec![
+            annotation("secret", (0, 0), (200, 100)),
+            annotation("secret", (10, 10), (50, 30)),
+        ],
+    };
+
+    let anonymizer = image_anonymizer::Anonymizer::builder()
+        .text_detector(detector)
+        .classifier(RuleBasedClassifier::default())
+        .mask_texts(["secret"])
+        .build()?;
+
+    let report = anonymizer.process_file(&image_path, &output_dir)?;
+    let output_path = output_dir.join("masked_test_image.png");
+    assert_eq!(report.output.as_ref(), Some(&output_path));
+
+    let output = image::open(output_path)?;
+    assert_eq!(output.get_pixel(20, 20), Rgba([0, 0, 0, 128]));
+    assert_eq!(output.get_pixel(100, 60), Rgba([255, 255, 255, 255]));
+
+    teardown(&test_dir)?;
+
+    Ok(())
+}
+
+// Face detector returning a fixed face with a configurable confidence
+struct StaticFaceDetector {
+    confidence: f32,
+}
+
+impl FaceDetector for StaticFaceDetector {
+    fn detect_faces(&self, _image: &DynamicImage) -> image_anonymizer::Result<Vec<FaceAnnotation>> {
+        Ok(vec![FaceAnnotation {
+            bounding_poly: Some(FaceBoundingPoly {
+                vertices: vec![
+                    FaceVertex { x: 100, y: 20 },
+                    FaceVertex { x: 150, y: 20 },
+                    FaceVertex { x: 150, y: 80 },
+                    FaceVertex { x: 100, y: 80 },
+                ],
+            }),
+            landmarks: None,
+            detection_confidence: Some(self.confidence),
+        }])
+    }
+}
+
+#[test]
+fn test_anonymizer_builder_options() -> Result<()> {
+    let anonymizer = image_anonymizer::Anonymizer::builder()
+        .text_detector(StaticTextDetector {
+            annotations: vec![
+                annotation("secret", (0, 0), (200, 100)),
+                annotation("secret", (10, 10), (50, 30)),
+            ],
+        })
+        .classifier(RuleBasedClassifier::default())
+        .face_detector(StaticFaceDetector { confidence: 0.9 })
+        .mask_texts(["secret"])
+        .text_mask_style(MaskStyle::Solid([255, 0, 0, 255]))
+        .face_mask_style(MaskStyle::Solid([0, 0, 255, 255]))
+        .build()?;
+
+    let mut img = DynamicImage::new_rgba8(200, 100);
+    anonymizer.mask(&mut img)?;
+    assert_eq!(img.get_pixel(20, 20), Rgba([255, 0, 0, 255]));
+    assert_eq!(img.get_pixel(120, 50), Rgba([0, 0, 255, 255]));
+
+    // Faces below the confidence threshold are left untouched
+    let anonymizer = image_anonymizer::Anonymizer::builder()
+        .text_detector(StaticTextDetector {
+            annotations: vec![],
+        })
+        .classifier(RuleBasedClassifier::default())
+        .face_detector(StaticFaceDetector { confidence: 0.4 })
+        .min_face_confidence(0.5)
+        .face_mask_style(MaskStyle::Solid([0, 0, 255, 255]))
+        .build()?;
+
+    let mut img = DynamicImage::new_rgba8(200, 100);
+    anonymizer.mask(&mut img)?;
+    assert_eq!(img.get_pixel(120, 50), Rgba([0, 0, 0, 0]));
+
+    Ok(())
+}
+
+#[test]
+fn test_anonymize_in_memory() -> Result<()> {
+    let anonymizer = image_anonymizer::Anonymizer::builder()
+        .text_detector(StaticTextDetector {
+            annotations: vec![
+                annotation("secret", (0, 0), (200, 100)),
+                annotation("secret", (10, 10), (50, 30)),
+            ],
+        })
+        .classifier(RuleBasedClassifier::default())
+        .mask_texts(["secret"])
+        .build()?;
+
+    let mut original = DynamicImage::new_rgba8(200, 100);
+    for y in 0..100 {
+        for x in 0..200 {
+            original.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+        }
+    }
+
+    let (masked, report) = anonymizer.anonymize_image(&original)?;
+    assert_eq!(report.masked_text_regions().count(), 1);
+    assert_eq!(masked.get_pixel(20, 20), Rgba([0, 0, 0, 128]));
+    assert_eq!(original.get_pixel(20, 20), Rgba([255, 255, 255, 255]));
+
+    let mut png = Vec::new();
+    original.write_to(&mut Cursor::new(&mut png), ImageOutputFormat::Png)?;
+    let output = anonymizer.anonymize_bytes(&png)?;
+    assert_eq!(image::guess_format(&output)?, ImageFormat::Png);
+    let decoded = image::load_from_memory(&output)?;
+    assert_eq!(decoded.get_pixel(20, 20), Rgba([0, 0, 0, 128]));
+    assert_eq!(decoded.get_pixel(100, 60), Rgba([255, 255, 255, 255]));
+
+    assert!(anonymizer.anonymize_bytes(b"not an image").is_err());
+
+    Ok(())
+}
+
+#[test]
+fn test_errors_are_typed() -> Result<()> {
+    let anonymizer = image_anonymizer::Anonymizer::builder()
+        .text_detector(StaticTextDetector {
+            annotations: Vec::new(),
+        })
+        .classifier(RuleBasedClassifier::default())
+        .build()?;
+
+    let result = anonymizer.anonymize_bytes(b"not an image");
+    assert!(matches!(result, Err(Error::Decode(_))));
+
+    let result = anonymizer.process_file(Path::new("nonexistent.png"), Path::new("."));
+    assert!(matches!(result, Err(Error::Io { .. })));
+
+    let result = RuleBasedClassifier::empty().with_rule("broken", "(unclosed");
+    assert!(matches!(result, Err(Error::Config(_))));
+
+    Ok(())
+}
+
+#[test]
+fn test_http_errors_hide_request_url() -> Result<()> {
+    // Nothing listens on a port that was just released
+    let port = std::net::TcpListener::bind("127.0.0.1:0")?
+        .local_addr()?
+        .port();
+    let source =
+        reqwest::blocking::get(format!("http://127.0.0.1:{port}/v1?key=leaky")).unwrap_err();
+
+    let error = Error::http("Vision API", source);
+    assert!(matches!(error, Error::Http { .. }));
+    assert!(!error.to_string().contains("leaky"));
+
+    Ok(())
+}
+
+#[test]
+fn test_report_lists_findings() -> Result<()> {
+    let anonymizer = image_anonymizer::Anonymizer::builder()
+        .text_detector(StaticTextDetector {
+            annotations: vec![
+                annotation("secret Submit", (0, 0), (200, 100)),
+                annotation("secret", (10, 10), (50, 30)),
+                annotation("Submit", (60, 10), (100, 30)),
+            ],
+        })
+        .classifier(RuleBasedClassifier::default())
+        .face_detector(StaticFaceDetector { confidence: 0.9 })
+        .mask_texts(["secret"])
+        .build()?;
+
+    let mut img = DynamicImage::new_rgba8(200, 100);
+    let report = anonymizer.mask(&mut img)?;
+
+    assert_eq!(report.texts.len(), 3);
+    assert!(report.texts[0].verdict.is_none());
+    assert!(report.texts[0].mask.is_none());
+
+    let secret = report.texts[1].verdict.as_ref().unwrap();
+    assert!(secret.sensitive);
+    assert_eq!(secret.classifier, "additional_masks");
+    let mask = report.texts[1].mask.unwrap();
+    assert_eq!(
+        (mask.min_x, mask.min_y, mask.max_x, mask.max_y),
+        (10, 10, 50, 30)
+    );
+
+    let submit = report.texts[2].verdict.as_ref().unwrap();
+    assert!(!submit.sensitive);
+    assert_eq!(submit.classifier, "rules");
+    assert!(report.texts[2].mask.is_none());
+    assert!(report.texts[2].bounds.is_some());
+
+    assert_eq!(report.faces.len(), 1);
+    assert_eq!(report.faces[0].confidence, Some(0.9));
+    assert!(report.faces[0].mask.is_some());
+
+    let json: serde_json::Value = serde_json::from_str(&report.to_json()?)?;
+    assert_eq!(
+        json["texts"][1]["verdict"]["classifier"],
+        "additional_masks"
+    );
+    assert_eq!(json["faces"][0]["mask"]["min_x"], 100);
+    assert!(json["timings"]["text_detection_ms"].is_number());
+
+    Ok(())
+}
+
+#[test]
+fn test_phrases_span_several_words() -> Result<()> {
+    let anonymizer = image_anonymizer::Anonymizer::builder()
+        .text_detector(StaticTextDetector {
+            annotations: vec![
+                annotation("Contact John Smith", (0, 0), (400, 100)),
+                annotation("Contact", (10, 10), (50, 30)),
+                annotation("John", (60, 10), (90, 30)),
+                annotation("Smith", (100, 10), (140, 30)),
+                annotation("Call", (10, 50), (40, 70)),
+                annotation("+1", (50, 50), (60, 70)),
+                annotation("555", (70, 50), (90, 70)),
+                annotation("010", (100, 50), (120, 70)),
+                annotation("0199", (130, 50), (160, 70)),
+            ],
+        })
+        .classifier(RuleBasedClassifier::default())
+        .mask_texts(["John Smith"])
+        .build()?;
+
+    let mut img = DynamicImage::new_rgba8(400, 100);
+    let report = anonymizer.mask(&mut img)?;
+
+    let masked: Vec<_> = report
+        .texts
+        .iter()
+        .filter(|finding| finding.mask.is_some())
+        .map(|finding| finding.text.as_str())
+        .collect();
+    assert_eq!(masked, ["John", "Smith", "+1", "555", "010", "0199"]);
+
+    let john = report.texts[2].verdict.as_ref().unwrap();
+    assert_eq!(john.classifier, "additional_masks");
+    let phone = report.texts[6].verdict.as_ref().unwrap();
+    assert_eq!(phone.reason, "matched rule 'phone_number'");
+
+    Ok(())
+}
+
+#[test]
+fn test_mask_patterns_are_named_in_report() -> Result<()> {
+    let anonymizer = image_anonymizer::Anonymizer::builder()
+        .text_detector(StaticTextDetector {
+            annotations: vec![
+                annotation("Ref: TKT-1042", (0, 0), (200, 100)),
+                annotation("Ref:", (10, 10), (40, 30)),
+                annotation("TKT-1042", (50, 10), (120, 30)),
+            ],
+        })
+        .classifier(RuleBasedClassifier::empty())
+        .mask_patterns([MaskPattern::new("ticket", r"TKT-\d+")?])
+        .build()?;
+
+    let mut img = DynamicImage::new_rgba8(200, 100);
+    let report = anonymizer.mask(&mut img)?;
+
+    assert!(report.texts[1].mask.is_none());
+    let verdict = report.texts[2].verdict.as_ref().unwrap();
+    assert_eq!(verdict.reason, "matched mask pattern 'ticket'");
+    let record = AuditRecord::from_report(&report, false);
+    assert_eq!(record.regions[0].category, MaskCategory::MaskPattern);
+
+    Ok(())
+}
+
+#[test]
+fn test_values_of_form_labels_are_masked() -> Result<()> {
+    let anonymizer = image_anonymizer::Anonymizer::builder()
+        .text_detector(StaticTextDetector {
+            annotations: vec![
+                annotation("Password: hunter2", (0, 0), (400, 100)),
+                annotation("Password:", (10, 10), (80, 30)),
+                annotation("hunter2", (150, 10), (220, 30)),
+            ],
+        })
+        .classifier(RuleBasedClassifier::empty())
+        .build()?;
+
+    let mut img = DynamicImage::new_rgba8(400, 100);
+    let report = anonymizer.mask(&mut img)?;
+
+    assert!(report.texts[1].mask.is_none());
+    assert!(report.texts[2].mask.is_some());
+    let verdict = report.texts[2].verdict.as_ref().unwrap();
+    assert_eq!(verdict.reason, "value of label 'password'");
+    let record = AuditRecord::from_report(&report, false);
+    assert_eq!(record.regions[0].category, MaskCategory::LabeledValue);
+
+    Ok(())
+}
+
+#[test]
+fn test_audit_record_hides_text_by_default() -> Result<()> {
+    let test_dir = PathBuf::from("./test_output_audit");
+    let image_path = test_dir.join("test_image.png");
+    create_test_image(&image_path)?;
+
+    let anonymizer = image_anonymizer::Anonymizer::builder()
+        .text_detector(StaticTextDetector {
+            annotations: vec![
+                annotation("secret", (0, 0), (200, 100)),
+                annotation("secret", (10, 10), (50, 30)),
+            ],
+        })
+        .classifier(RuleBasedClassifier::default())
+        .face_detector(StaticFaceDetector { confidence: 0.9 })
+        .mask_texts(["secret"])
+        .build()?;
+    let report = anonymizer.process_file(&image_path, &test_dir.join("output"))?;
+
+    let record = AuditRecord::from_report(&report, false);
+    assert_eq!(record.input_sha256.as_ref().unwrap().len(), 64);
+    assert_ne!(record.input_sha256, record.output_sha256);
+    assert_eq!(record.regions.len(), 2);
+    assert_eq!(record.regions[0].category, MaskCategory::AdditionalMask);
+    assert_eq!(record.regions[1].category, MaskCategory::Face);
+    assert_eq!(record.regions[1].backend, "custom");
+
+    let json = serde_json::to_string(&record)?;
+    assert!(!json.contains("secret"));
+
+    let record = AuditRecord::from_report(&report, true);
+    assert_eq!(record.regions[0].text.as_deref(), Some("secret"));
+
+    teardown(&test_dir)?;
+
+    Ok(())
+}
+
+#[test]
+fn test_audit_record_of_failed_image() -> Result<()> {
+    let test_dir = PathBuf::from("./test_output_audit_failure");
+    fs::create_dir_all(&test_dir)?;
+    let image_path = test_dir.join("broken.png");
+    fs::write(&image_path, b"not an image")?;
+
+    let anonymizer = image_anonymizer::Anonymizer::builder()
+        .text_detector(StaticTextDetector {
+            annotations: Vec::new(),
+        })
+        .classifier(RuleBasedClassifier::default())
+        .build()?;
+    let error = anonymizer
+        .process_file(&image_path, &test_dir.join("output"))
+        .unwrap_err();
+
+    let record = AuditRecord::from_failure(&image_path, &error);
+    assert_eq!(record.input.as_deref(), Some(image_path.as_path()));
+    assert_eq!(record.input_sha256.as_ref().unwrap().len(), 64);
+    assert!(record.output.is_none() && !record.complete);
+    assert_eq!(record.error, Some(error.to_string()));
+
+    // A file that cannot be read has no hash
+    let missing = AuditRecord::from_failure(&test_dir.join("missing.png"), &error);
+    assert!(missing.input_sha256.is_none());
+
+    teardown(&test_dir)?;
+
+    Ok(())
+}
+
+// Face detector standing in for an unreachable API
+struct FailingFaceDetector;
+
+impl FaceDetector for FailingFaceDetector {
+    fn detect_faces(&self, _image: &DynamicImage) -> image_anonymizer::Result<Vec<FaceAnnotation>> {
+        Err(Error::Config("service unavailable".to_string()))
+    }
+}
+
+#[test]
+fn test_failure_policies() -> Result<()> {
+    let anonymizer = |policy| {
+        image_anonymizer::Anonymizer::builder()
+            .text_detector(StaticTextDetector {
+                annotations: vec![
+                    annotation("secret", (0, 0), (200, 100)),
+                    annotation("secret", (10, 10), (50, 30)),
+                ],
+            })
+            .classifier(RuleBasedClassifier::default())
+            .face_detector(FailingFaceDetector)
+            .mask_texts(["secret"])
+            .on_failure(policy)
+            .build()
+    };
+
+    // Lenient processing masks what it can and marks the report as partial
+    let mut img = DynamicImage::new_rgba8(200, 100);
+    let report = anonymizer(FailurePolicy::Lenient)?.mask(&mut img)?;
+    assert!(!report.is_complete());
+    assert_eq!(report.failures[0].stage, Stage::FaceDetection);
+    assert!(!report.blacked_out);
+    assert_eq!(img.get_pixel(20, 20), Rgba([0, 0, 0, 128]));
+    assert!(!AuditRecord::from_report(&report, false).complete);
+
+    let mut img = DynamicImage::new_rgba8(200, 100);
+    let result = anonymizer(FailurePolicy::Abort)?.mask(&mut img);
+    assert!(matches!(result, Err(Error::Config(_))));
+
+    let mut img = DynamicImage::new_rgba8(200, 100);
+    let report = anonymizer(FailurePolicy::Blackout)?.mask(&mut img)?;
+    assert!(report.blacked_out);
+    assert!(!report.is_complete());
+    assert_eq!(img.get_pixel(150, 90), Rgba([0, 0, 0, 255]));
+
+    Ok(())
+}
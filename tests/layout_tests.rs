@@ -0,0 +1,173 @@
+use image_anonymizer::ocr::detection::{BoundingPoly, TextAnnotation, Vertex};
+use image_anonymizer::ocr::layout::{
+    COMMON_COLUMN_HEADERS, DEFAULT_VALUE_LABELS, find_column_cells, find_labeled_values,
+    group_lines,
+};
+
+fn annotation(description: &str, min: (i32, i32), max: (i32, i32)) -> TextAnnotation {
+    TextAnnotation {
+        description: description.to_string(),
+        bounding_poly: Some(BoundingPoly {
+            vertices: vec![
+                Vertex { x: min.0, y: min.1 },
+                Vertex { x: max.0, y: min.1 },
+                Vertex { x: max.0, y: max.1 },
+                Vertex { x: min.0, y: max.1 },
+            ],
+        }),
+    }
+}
+
+#[test]
+fn test_group_lines() {
+    let annotations = vec![
+        annotation("whole text", (0, 0), (400, 100)),
+        // Slightly slanted second word, listed before the first one
+        annotation("Smith", (60, 12), (100, 32)),
+        annotation("John", (10, 10), (50, 30)),
+        // Far to the right, like another table column
+        annotation("Admin", (300, 10), (340, 30)),
+        annotation("Phone", (10, 50), (50, 70)),
+    ];
+
+    let lines = group_lines(&annotations);
+    let texts: Vec<_> = lines.iter().map(|line| line.text.as_str()).collect();
+    assert_eq!(texts, ["John Smith", "Admin", "Phone"]);
+
+    let first = &lines[0];
+    assert_eq!(first.words[0].annotation, 2);
+    assert_eq!(first.words[1].annotation, 1);
+    assert_eq!(first.words[1].range, 5..10);
+    assert_eq!((first.bounds.min_x, first.bounds.max_y), (10, 32));
+
+    let words: Vec<_> = first.words_in(&(3..6)).map(|w| w.annotation).collect();
+    assert_eq!(words, [2, 1]);
+}
+
+#[test]
+fn test_single_annotation_is_a_line() {
+    let lines = group_lines(&[annotation("alone", (0, 0), (10, 10))]);
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0].text, "alone");
+    assert!(group_lines(&[]).is_empty());
+}
+
+#[test]
+fn test_find_labeled_values() {
+    let annotations = vec![
+        annotation("whole text", (0, 0), (600, 400)),
+        // Value on the same line after a colon
+        annotation("Password:", (10, 10), (90, 30)),
+        annotation("hunter2", (100, 10), (160, 30)),
+        // Value in another column of the same row
+        annotation("API", (10, 50), (40, 70)),
+        annotation("Key:", (45, 50), (80, 70)),
+        annotation("sk-local-1234", (150, 50), (270, 70)),
+        // Value below the label
+        annotation("Email:", (10, 100), (60, 120)),
+        annotation("jane@corp", (10, 130), (100, 150)),
+        // A generic word without a colon, such as a table header, labels nothing
+        annotation("Phone", (300, 100), (350, 120)),
+        annotation("Name", (450, 100), (500, 120)),
+        // Too far to the right to be the value
+        annotation("Token:", (10, 300), (60, 320)),
+        annotation("Help", (500, 300), (540, 320)),
+        // A label inside a sentence labels nothing
+        annotation("Forgot", (10, 200), (60, 220)),
+        annotation("password?", (65, 200), (140, 220)),
+        annotation("Reset", (10, 230), (60, 250)),
+    ];
+    let labels: Vec<String> = DEFAULT_VALUE_LABELS.iter().map(|l| l.to_string()).collect();
+
+    let values = find_labeled_values(&annotations, &labels);
+    assert_eq!(
+        values,
+        [
+            (2, "password".to_string()),
+            (5, "api key".to_string()),
+            (7, "email".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn test_menu_items_are_not_labels() {
+    // A settings navigation bar, each entry next to the other ones
+    let annotations = vec![
+        annotation("whole text", (0, 0), (600, 100)),
+        annotation("Profile", (10, 10), (70, 30)),
+        annotation("Password", (120, 10), (200, 30)),
+        annotation("Notifications", (250, 10), (360, 30)),
+        annotation("Access", (410, 10), (470, 30)),
+        annotation("Token", (475, 10), (525, 30)),
+        annotation("Billing", (10, 40), (70, 60)),
+        annotation("Security", (120, 40), (190, 60)),
+    ];
+    let labels: Vec<String> = DEFAULT_VALUE_LABELS.iter().map(|l| l.to_string()).collect();
+
+    assert!(find_labeled_values(&annotations, &labels).is_empty());
+}
+
+#[test]
+fn test_find_column_cells() {
+    let annotations = vec![
+        annotation("whole text", (0, 0), (600, 400)),
+        annotation("Name", (10, 10), (60, 30)),
+        annotation("Email", (200, 10), (250, 30)),
+        annotation("Phone", (400, 10), (450, 30)),
+        annotation("Jane", (10, 50), (50, 70)),
+        annotation("jane@corp.com", (200, 50), (320, 70)),
+        annotation("555", (400, 50), (430, 70)),
+        annotation("0100", (435, 50), (475, 70)),
+        annotation("Bob", (10, 90), (40, 110)),
+        annotation("bob@corp.com", (200, 90), (310, 110)),
+        annotation("555", (400, 90), (430, 110)),
+        annotation("0199", (435, 90), (475, 110)),
+        // Past the end of the table
+        annotation("Showing", (150, 300), (220, 320)),
+        annotation("2", (225, 300), (235, 320)),
+    ];
+    let headers: Vec<String> = COMMON_COLUMN_HEADERS
+        .iter()
+        .map(|h| h.to_string())
+        .collect();
+
+    let cells = find_column_cells(&annotations, &headers);
+    assert_eq!(
+        cells,
+        [
+            (5, "email".to_string()),
+            (9, "email".to_string()),
+            (6, "phone".to_string()),
+            (7, "phone".to_string()),
+            (10, "phone".to_string()),
+            (11, "phone".to_string()),
+        ]
+    );
+    assert!(find_column_cells(&annotations, &[]).is_empty());
+}
+
+#[test]
+fn test_column_headers_in_prose_mask_nothing() {
+    let annotations = vec![
+        annotation("whole text", (0, 0), (600, 100)),
+        annotation("Every", (10, 10), (55, 30)),
+        annotation("customer", (60, 10), (130, 30)),
+        annotation("email", (135, 10), (180, 30)),
+        annotation("is", (185, 10), (200, 30)),
+        annotation("answered", (205, 10), (280, 30)),
+        annotation("quickly", (10, 40), (70, 60)),
+        annotation("and", (75, 40), (105, 60)),
+        annotation("politely", (110, 40), (170, 60)),
+        annotation("by", (175, 40), (195, 60)),
+        annotation("phone.", (200, 40), (250, 60)),
+        annotation("Thanks", (10, 70), (70, 90)),
+        annotation("again", (75, 70), (120, 90)),
+    ];
+    let headers: Vec<String> = COMMON_COLUMN_HEADERS
+        .iter()
+        .map(|h| h.to_string())
+        .collect();
+
+    assert!(find_column_cells(&annotations, &headers).is_empty());
+}
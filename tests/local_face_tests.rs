@@ -0,0 +1,63 @@
+#![cfg(feature = "local-faces")]
+
+use anyhow::Result;
+use image::{DynamicImage, GenericImage, Rgba};
+use image_anonymizer::face::detection::FaceDetector;
+use image_anonymizer::face::local::LocalFaceDetector;
+
+// Load an image shipped with the repository
+fn load_example(name: &str) -> DynamicImage {
+    let path = format!("{}/examples/{}", env!("CARGO_MANIFEST_DIR"), name);
+    image::open(path).expect("example image must decode")
+}
+
+#[test]
+fn test_local_face_detector_finds_photo_faces() -> Result<()> {
+    let img = load_example("face_before.jpg");
+
+    let faces = LocalFaceDetector::default().detect_faces(&img)?;
+
+    assert!(faces.len() >= 10, "found only {} faces", faces.len());
+    assert!(
+        faces
+            .iter()
+            .all(|face| face.detection_confidence.unwrap() >= 0.5)
+    );
+
+    // The man with glasses on the left and the woman on the right
+    for (x, y) in [(155, 317), (776, 347)] {
+        let covered = faces.iter().any(|face| {
+            let vertices = &face.bounding_poly.as_ref().unwrap().vertices;
+            (vertices[0].x..=vertices[2].x).contains(&x)
+                && (vertices[0].y..=vertices[2].y).contains(&y)
+        });
+        assert!(covered, "no face found around ({}, {})", x, y);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_local_face_detector_ignores_screenshot() -> Result<()> {
+    let img = load_example("masked_text1.png");
+
+    let faces = LocalFaceDetector::default().detect_faces(&img)?;
+    assert!(faces.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn test_local_face_detector_ignores_plain_image() -> Result<()> {
+    let mut img = DynamicImage::new_rgba8(100, 100);
+    for y in 0..100 {
+        for x in 0..100 {
+            img.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+        }
+    }
+
+    let faces = LocalFaceDetector::default().detect_faces(&img)?;
+    assert!(faces.is_empty());
+
+    Ok(())
+}
@@ -1,7 +1,9 @@
 use anyhow::Result;
 use image::{DynamicImage, GenericImage, GenericImageView, Rgba};
 use image_anonymizer::ocr::detection::{BoundingPoly, TextAnnotation, Vertex};
-use image_anonymizer::ocr::masking::mask_text;
+use image_anonymizer::ocr::document::SymbolBox;
+use image_anonymizer::ocr::mask_list::{MaskList, parse_mask_patterns, parse_mask_terms};
+use image_anonymizer::ocr::masking::{mask_text, masked_parts};
 
 #[test]
 fn test_mask_text_with_empty_annotations() -> Result<()> {
@@ -84,3 +86,126 @@ fn test_mask_text_with_annotations() -> Result<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_mask_only_matching_part() -> Result<()> {
+    let mut img = DynamicImage::new_rgba8(400, 40);
+    for y in 0..40 {
+        for x in 0..400 {
+            img.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+        }
+    }
+
+    // 16 characters over 160 pixels, the value starts at the 7th character
+    let annotations = vec![TextAnnotation {
+        description: "email:john@x.com".to_string(),
+        bounding_poly: Some(BoundingPoly {
+            vertices: vec![
+                Vertex { x: 0, y: 0 },
+                Vertex { x: 160, y: 0 },
+                Vertex { x: 160, y: 9 },
+                Vertex { x: 0, y: 9 },
+            ],
+        }),
+    }];
+    mask_text(&mut img, &annotations, &["john@x.com".to_string()])?;
+
+    let white = Rgba([255, 255, 255, 255]);
+    assert_eq!(img.get_pixel(30, 5), white, "the label stays visible");
+    assert_eq!(img.get_pixel(59, 5), white);
+    assert_eq!(img.get_pixel(60, 5), Rgba([0, 0, 0, 128]));
+    assert_eq!(img.get_pixel(160, 9), Rgba([0, 0, 0, 128]));
+    assert_eq!(img.get_pixel(161, 5), white);
+
+    Ok(())
+}
+
+#[test]
+fn test_masked_parts_use_symbol_boxes() {
+    let square = |x: i32| BoundingPoly {
+        vertices: vec![
+            Vertex { x, y: 0 },
+            Vertex { x: x + 5, y: 0 },
+            Vertex { x: x + 5, y: 9 },
+            Vertex { x, y: 9 },
+        ],
+    };
+    // Narrow "i" and "d", wide "ü" (two bytes)
+    let annotation = TextAnnotation {
+        description: "id:ü".to_string(),
+        bounding_poly: Some(BoundingPoly {
+            vertices: vec![Vertex { x: 0, y: 0 }, Vertex { x: 99, y: 9 }],
+        }),
+    };
+    let symbols = [(0..1, 0), (1..2, 10), (2..3, 20), (3..5, 60)]
+        .into_iter()
+        .map(|(range, x)| SymbolBox {
+            range,
+            bounding_poly: square(x),
+        })
+        .collect::<Vec<_>>();
+
+    let parts = masked_parts(&annotation, &symbols, &[3..5, 0..1], 400, 40);
+    assert_eq!(parts.len(), 2);
+    assert_eq!(parts[0].range, 0..1);
+    assert_eq!((parts[0].region.min_x, parts[0].region.max_x), (0, 5));
+    assert_eq!(parts[1].range, 3..5);
+    assert_eq!((parts[1].region.min_x, parts[1].region.max_x), (60, 65));
+
+    // Parts covering the whole text mask the whole annotation
+    assert!(masked_parts(&annotation, &symbols, &[0..2, 2..5], 400, 40).is_empty());
+}
+
+#[test]
+fn test_parse_mask_patterns() -> Result<()> {
+    let patterns = parse_mask_patterns(
+        "# internal identifiers\n\nticket = TKT-\\d+\n[A-Z]{3}-\\d{4}\nsession=id=\\w+\n",
+    )?;
+    let names: Vec<_> = patterns.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["ticket", "[A-Z]{3}-\\d{4}", "session"]);
+
+    let list = MaskList::new(["secret"]).with_patterns(patterns);
+    let found = list.find("secret TKT-42 ABC-1234 id=x1");
+    let ranges: Vec<_> = found.iter().map(|(range, _)| range.clone()).collect();
+    assert_eq!(ranges, [0..6, 7..13, 14..22, 23..28]);
+    assert_eq!(found[1].1.reason, "matched mask pattern 'ticket'");
+    assert_eq!(found[1].1.classifier, "mask_patterns");
+
+    let err = parse_mask_patterns("ok = a+\nbroken = (").unwrap_err();
+    assert!(err.to_string().contains("line 2"), "{}", err);
+
+    Ok(())
+}
+
+#[test]
+fn test_mask_terms_ignore_case_and_compatibility_forms() {
+    let terms = parse_mask_terms("# customers\nAcme Corp\n\n  db01.internal  \nﬁle\n");
+    assert_eq!(terms, ["Acme Corp", "db01.internal", "ﬁle"]);
+
+    let list = MaskList::new(terms);
+    let text = "Bill ＡＣＭＥ corp via DB01.INTERNAL, file profile";
+    let matched = |list: &MaskList| {
+        list.find(text)
+            .into_iter()
+            .map(|(range, _)| &text[range])
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        matched(&list),
+        ["ＡＣＭＥ corp", "DB01.INTERNAL", "file", "file"]
+    );
+    let secret = MaskList::new(["secret"]);
+    assert_eq!(secret.find("topsecret secret123").len(), 2);
+
+    // Terms match inside words unless whole words are required
+    let list = list.with_whole_words(true);
+    assert_eq!(matched(&list), ["ＡＣＭＥ corp", "DB01.INTERNAL", "file"]);
+    let names = MaskList::new(["Ann", "Lee"]).with_whole_words(true);
+    assert!(names.check("Annual report for the Fleet").is_none());
+    assert_eq!(names.find("Ann Lee, (Lee)").len(), 3);
+    assert_eq!(
+        list.check("acme corp").unwrap().reason,
+        "contains additional mask text 'Acme Corp'"
+    );
+    assert!(list.check("Acme Co").is_none());
+}
@@ -0,0 +1,80 @@
+use image_anonymizer::ocr::masking::SensitiveTextCriteria;
+use image_anonymizer::ocr::pii::find_pii;
+
+fn found<'a>(text: &'a str, criteria: &SensitiveTextCriteria) -> Vec<(&'a str, String)> {
+    find_pii(text, criteria)
+        .into_iter()
+        .map(|(range, verdict)| (&text[range], verdict.reason))
+        .collect()
+}
+
+#[test]
+fn test_find_contact_details_and_cards() {
+    let criteria = SensitiveTextCriteria::default();
+    let text = "Mail jane.doe+work@mail.example.co.uk or call +44 20 7946 0958, \
+                (555) 123-4567. Card 4111 1111 1111 1111, not 4111 1111 1111 1112";
+    assert_eq!(
+        found(text, &criteria),
+        [
+            (
+                "jane.doe+work@mail.example.co.uk",
+                "detected email address".to_string()
+            ),
+            ("+44 20 7946 0958", "detected phone number".to_string()),
+            ("(555) 123-4567", "detected phone number".to_string()),
+            (
+                "4111 1111 1111 1111",
+                "detected credit card number".to_string()
+            ),
+        ]
+    );
+
+    // Dates, versions and short numbers are not phone numbers
+    assert!(found("on 2024-01-15, v1.2.3, order 42", &criteria).is_empty());
+    assert!(found("user@localhost", &criteria).is_empty());
+
+    // Dotted runs such as IP addresses and versions, or mixed separators
+    for text in [
+        "host 192.168.100.20 is up",
+        "release 1.22.333.4444",
+        "serial 555.123-4567",
+    ] {
+        assert!(found(text, &criteria).is_empty(), "{}", text);
+    }
+    assert_eq!(found("call 555.123.4567.", &criteria).len(), 1);
+}
+
+#[test]
+fn test_find_names_and_companies() {
+    let criteria = SensitiveTextCriteria::default();
+    let text = "Signed by Dr. Ada Lovelace for Analytical Engines Ltd.";
+    let kinds: Vec<_> = found(text, &criteria);
+    assert_eq!(
+        kinds[0],
+        ("Dr. Ada Lovelace", "detected personal name".to_string())
+    );
+    assert_eq!(
+        kinds[1],
+        (
+            "Analytical Engines Ltd.",
+            "detected company name".to_string()
+        )
+    );
+}
+
+#[test]
+fn test_criteria_disable_detectors() {
+    let criteria = SensitiveTextCriteria {
+        emails: false,
+        personal_names: false,
+        ..SensitiveTextCriteria::default()
+    };
+    let text = "Mr. Smith, smith@example.com, 4111-1111-1111-1111";
+    assert_eq!(
+        found(text, &criteria),
+        [(
+            "4111-1111-1111-1111",
+            "detected credit card number".to_string()
+        )]
+    );
+}
@@ -0,0 +1,49 @@
+use anyhow::Result;
+use image_anonymizer::ocr::mask_list::MaskList;
+use image_anonymizer::ocr::secret_hashes::{hash_secret, parse_secret_hashes};
+
+fn hashes_file(salt: &str, secrets: &[&str]) -> String {
+    let mut contents = format!("# deploy credentials\nsalt = {}\n", salt);
+    for secret in secrets {
+        contents.push_str(&hash_secret(salt, secret));
+        contents.push('\n');
+    }
+    contents
+}
+
+#[test]
+fn test_hashed_secrets_are_found() -> Result<()> {
+    let secrets = parse_secret_hashes(&hashes_file("pepper", &["hunter2", "sk-12 34 56"]))?;
+    assert_eq!(secrets.len(), 2);
+
+    let list = MaskList::default().with_secret_hashes(secrets);
+    let text = "pw:hunter2 token=hunter2 key sk-12 34 56 hunter";
+    let found: Vec<_> = list
+        .find(text)
+        .into_iter()
+        .map(|(range, verdict)| {
+            assert_eq!(verdict.classifier, "hashed_secrets");
+            &text[range]
+        })
+        .collect();
+    assert_eq!(found, ["hunter2", "hunter2", "sk-12 34 56"]);
+
+    // Split by the OCR without spaces in the secret
+    let secrets = parse_secret_hashes(&hashes_file("pepper", &["AKIAXYZ123"]))?;
+    let list = MaskList::default().with_secret_hashes(secrets);
+    assert!(list.check("key AKIA XYZ123").is_some());
+    assert!(list.check("key AKIAXYZ12").is_none());
+
+    Ok(())
+}
+
+#[test]
+fn test_parse_secret_hashes_errors() {
+    let digest = hash_secret("pepper", "hunter2");
+    let err = parse_secret_hashes(&format!("{}\n", digest)).unwrap_err();
+    assert!(err.to_string().contains("line 1"), "{}", err);
+
+    let err =
+        parse_secret_hashes(&format!("salt = pepper\n{}\nnot-a-digest\n", digest)).unwrap_err();
+    assert!(err.to_string().contains("line 3"), "{}", err);
+}